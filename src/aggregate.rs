@@ -0,0 +1,217 @@
+//! Several expanders presented as a single device.
+//!
+//! Pins are numbered globally across the devices: global pin `g` lives on device
+//! `g / C::PINS` at index `g % C::PINS` (`port * 8 + pin`). Bulk operations take and return
+//! one word per device, where bit `i` of word `d` is global pin `d * C::PINS + i`.
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+
+use crate::{Bank, Chip, Device, IoPin, Pcal6416a, Pcal6416aDevice, Pcal6416aError, Pin, Port, SharedDevice};
+
+/// `N` expanders of the same chip, addressed through a single global pin numbering.
+///
+/// # Example
+/// ```ignore
+/// let low = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2c_low));
+/// let high = Device::new(Pcal6416aDevice::new(AddrPinState::High, i2c_high));
+/// let expanders = MultiDevice::new([low, high]);
+///
+/// let pins = expanders.split();
+/// let pins = pins.as_flattened();
+/// pins[20].set_high_async().await?; // device 1, port 0, pin 4
+/// ```
+pub struct MultiDevice<I2c: embedded_hal_async::i2c::I2c, M: RawMutex, const N: usize, C: Chip = Pcal6416a> {
+    devices: [SharedDevice<I2c, M, C>; N],
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex, const N: usize, C: Chip> MultiDevice<I2c, M, N, C> {
+    /// Create an aggregate from `N` devices, device `d` providing global pins
+    /// `d * C::PINS` to `(d + 1) * C::PINS - 1`.
+    pub fn new(devices: [Device<Pcal6416aDevice<I2c, C>>; N]) -> Self {
+        Self {
+            devices: devices.map(SharedDevice::new),
+        }
+    }
+
+    /// Total number of pins across all devices
+    #[must_use]
+    pub const fn pin_count(&self) -> usize {
+        N * C::PINS
+    }
+
+    /// Get the shared device at `index`
+    #[must_use]
+    pub fn device(&self, index: usize) -> Option<&SharedDevice<I2c, M, C>> {
+        self.devices.get(index)
+    }
+
+    /// Locate a global pin, returning the device index, port and pin
+    #[must_use]
+    pub fn locate(&self, global: usize) -> Option<(usize, Port, Pin)> {
+        if global >= self.pin_count() {
+            return None;
+        }
+
+        let local = global % C::PINS;
+        #[allow(clippy::cast_possible_truncation)] // local < 24
+        let (port, pin) = (Port::from_index((local / 8) as u8)?, Pin::from_bit((local % 8) as u8)?);
+        Some((global / C::PINS, port, pin))
+    }
+
+    /// Get the pin with global number `global`
+    #[must_use]
+    pub fn pin(&self, global: usize) -> Option<IoPin<'_, I2c, M, C>> {
+        let (device, port, pin) = self.locate(global)?;
+        Some(IoPin::new(port, pin, &self.devices[device]))
+    }
+
+    /// Read the input state of every device
+    ///
+    /// The samples go through the input snapshot of each device, so edges found by the
+    /// reads still reach the waiting pins.
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn read_inputs(&self) -> Result<[u32; N], Pcal6416aError<I2c::Error>> {
+        let mut words = [0; N];
+        for (word, device) in words.iter_mut().zip(&self.devices) {
+            *word = device.sample_inputs().await?.levels;
+        }
+
+        Ok(words)
+    }
+
+    /// Read the output register state of every device
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn read_outputs(&self) -> Result<[u32; N], Pcal6416aError<I2c::Error>> {
+        let mut words = [0; N];
+        for (word, device) in words.iter_mut().zip(&self.devices) {
+            *word = device.device.lock().await.read_bank_async(Bank::Output).await?;
+        }
+
+        Ok(words)
+    }
+
+    /// Drive the outputs selected by `mask` to the matching bits of `values`
+    ///
+    /// Devices with an empty mask are not accessed.
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn write_outputs(&self, mask: [u32; N], values: [u32; N]) -> Result<(), Pcal6416aError<I2c::Error>> {
+        for ((device, mask), values) in self.devices.iter().zip(mask).zip(values) {
+            if mask == 0 {
+                continue;
+            }
+
+            device.write_outputs(mask, values).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex, const N: usize> MultiDevice<I2c, M, N, Pcal6416a> {
+    /// Split all devices into their individual pins
+    ///
+    /// Entry `[d][i]` is global pin `d * 16 + i`; use `as_flattened()` to index the
+    /// pins by global number directly.
+    pub fn split(&self) -> [[IoPin<'_, I2c, M>; 16]; N] {
+        self.devices.each_ref().map(|device| device.split())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::future::{Future, poll_fn};
+    use core::task::Poll;
+
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embedded_hal_async::digital::Wait;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use super::*;
+    use crate::AddrPinState;
+
+    #[tokio::test]
+    async fn global_pin_numbering() {
+        let low = Mock::new(&[Transaction::write_read(0x20, vec![0x00], vec![0b0000_0000])]);
+        let high = Mock::new(&[
+            Transaction::write_read(0x21, vec![0x01], vec![0b0000_0100]),
+            Transaction::write_read(0x21, vec![0x02], vec![0b0000_0000]),
+            Transaction::write(0x21, vec![0x02, 0b0000_0010]),
+        ]);
+        let dev: MultiDevice<_, NoopRawMutex, 2> = MultiDevice::new([
+            Device::new(Pcal6416aDevice::new(AddrPinState::Low, low)),
+            Device::new(Pcal6416aDevice::new(AddrPinState::High, high)),
+        ]);
+
+        assert_eq!(dev.pin_count(), 32);
+        assert_eq!(dev.locate(26), Some((1, Port::Port1, Pin::Pin2)));
+        assert!(dev.locate(32).is_none());
+        assert!(dev.pin(32).is_none());
+
+        assert!(dev.pin(26).unwrap().is_high_async().await.unwrap());
+        {
+            let pins = dev.split();
+            let pins = pins.as_flattened();
+            assert_eq!(pins.len(), 32);
+            assert_eq!(pins[17].index(), 1);
+            pins[17].set_high_async().await.unwrap();
+            assert!(pins[0].is_low_async().await.unwrap());
+        }
+
+        for device in &dev.devices {
+            device.device.lock().await.interface.i2cbus.done();
+        }
+    }
+
+    #[tokio::test]
+    async fn bulk_read_write() {
+        let low = Mock::new(&[
+            Transaction::write_read(0x20, vec![0x00], vec![0x34, 0x12]),
+            Transaction::write_read(0x20, vec![0x02], vec![0xFF, 0xFF]),
+            Transaction::write(0x20, vec![0x02, 0xF0, 0xFF]),
+        ]);
+        let high = Mock::new(&[Transaction::write_read(0x21, vec![0x00], vec![0x78, 0x56])]);
+        let dev: MultiDevice<_, NoopRawMutex, 2> = MultiDevice::new([
+            Device::new(Pcal6416aDevice::new(AddrPinState::Low, low)),
+            Device::new(Pcal6416aDevice::new(AddrPinState::High, high)),
+        ]);
+
+        assert_eq!(dev.read_inputs().await.unwrap(), [0x1234, 0x5678]);
+        dev.write_outputs([0x000F, 0], [0x0000, 0xFFFF]).await.unwrap();
+
+        for device in &dev.devices {
+            device.device.lock().await.interface.i2cbus.done();
+        }
+    }
+
+    #[tokio::test]
+    async fn read_inputs_keeps_edges() {
+        let low = Mock::new(&[
+            Transaction::write_read(0x20, vec![0x00], vec![0x00]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x01, 0x00]),
+        ]);
+        let high = Mock::new(&[Transaction::write_read(0x21, vec![0x00], vec![0x00, 0x00])]);
+        let dev: MultiDevice<_, NoopRawMutex, 2> = MultiDevice::new([
+            Device::new(Pcal6416aDevice::new(AddrPinState::Low, low)),
+            Device::new(Pcal6416aDevice::new(AddrPinState::High, high)),
+        ]);
+
+        let mut pin = dev.pin(0).unwrap();
+        {
+            // The rising edge found by the bulk read reaches the waiting pin
+            let mut edge = core::pin::pin!(pin.wait_for_rising_edge());
+            assert!(poll_fn(|cx| Poll::Ready(edge.as_mut().poll(cx).is_pending())).await);
+            assert_eq!(dev.read_inputs().await.unwrap(), [0x0001, 0x0000]);
+            edge.await.unwrap();
+        }
+
+        for device in &dev.devices {
+            device.device.lock().await.interface.i2cbus.done();
+        }
+    }
+}
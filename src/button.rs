@@ -0,0 +1,300 @@
+//! Button gesture recognition.
+//!
+//! [`Button`] debounces an [`IoPin`] and turns its presses into [`ButtonEvent`]s: press,
+//! release, long press, repeat while held and double click. As with [`Debounced`], the
+//! device has to be serviced or polled for presses to be seen.
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embedded_hal_async::delay::DelayNs;
+
+use crate::timeout::with_timeout;
+use crate::{Chip, DEFAULT_SETTLE_MS, Debounced, IoPin, Pcal6416aError};
+
+/// Gesture reported by a [`Button`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ButtonEvent {
+    /// The button went down
+    Press,
+    /// The button went up
+    Release,
+    /// The button has been held for [`ButtonConfig::long_press_ms`]
+    LongPress,
+    /// The button is still held, every [`ButtonConfig::repeat_ms`] after a long press
+    Repeat,
+    /// The button went down a second time within [`ButtonConfig::double_click_ms`] of a
+    /// short press; follows the [`ButtonEvent::Press`] of that second click
+    DoubleClick,
+}
+
+/// Timings and wiring of a [`Button`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ButtonConfig {
+    /// The button pulls the input low when pressed
+    ///
+    /// Applies on top of the Polarity Inversion register, whose effect is already part of
+    /// the input level.
+    pub active_low: bool,
+    /// Debounce settle time, in milliseconds
+    pub settle_ms: u32,
+    /// Hold time before a long press, in milliseconds
+    pub long_press_ms: u32,
+    /// Interval between repeats after a long press, in milliseconds, `None` to disable
+    pub repeat_ms: Option<u32>,
+    /// Longest gap between the release of a click and the next press for a double click,
+    /// in milliseconds
+    pub double_click_ms: u32,
+}
+
+impl Default for ButtonConfig {
+    fn default() -> Self {
+        Self {
+            active_low: true,
+            settle_ms: DEFAULT_SETTLE_MS,
+            long_press_ms: 500,
+            repeat_ms: None,
+            double_click_ms: 300,
+        }
+    }
+}
+
+/// Front-panel button on an expander input.
+///
+/// # Example
+/// ```ignore
+/// let [button, ..] = shared.split();
+/// let mut button = Button::new(button, embassy_time::Delay).with_config(ButtonConfig {
+///     repeat_ms: Some(100),
+///     ..ButtonConfig::default()
+/// });
+///
+/// loop {
+///     match button.next_event().await? {
+///         ButtonEvent::Press | ButtonEvent::Repeat => volume_up(),
+///         ButtonEvent::DoubleClick => mute(),
+///         _ => {}
+///     }
+/// }
+/// ```
+pub struct Button<'a, D: DelayNs + Clone, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> {
+    input: Debounced<'a, D, I2c, M, C>,
+    timer: D,
+    config: ButtonConfig,
+    /// Long press already reported for the current press
+    long_pressed: bool,
+    /// The current press is the second click of a double click
+    second_click: bool,
+    /// A short press was released and the double click window is open
+    click_pending: bool,
+    /// Event to report on the next call
+    pending: Option<ButtonEvent>,
+    /// Whether the button is pressed as far as reported events go, `None` before the first
+    /// call
+    pressed: Option<bool>,
+}
+
+impl<'a, D: DelayNs + Clone, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> Button<'a, D, I2c, M, C> {
+    /// Create a button on `pin` with the default configuration, timing with `delay`
+    pub fn new(pin: IoPin<'a, I2c, M, C>, delay: D) -> Self {
+        let config = ButtonConfig::default();
+        Self {
+            input: Debounced::new(pin, delay.clone()).with_settle_ms(config.settle_ms),
+            timer: delay,
+            config,
+            long_pressed: false,
+            second_click: false,
+            click_pending: false,
+            pending: None,
+            pressed: None,
+        }
+    }
+
+    /// Set the timings and wiring of the button
+    #[must_use]
+    pub fn with_config(mut self, config: ButtonConfig) -> Self {
+        self.set_config(config);
+        self
+    }
+
+    /// Get the timings and wiring of the button
+    #[must_use]
+    pub const fn config(&self) -> &ButtonConfig {
+        &self.config
+    }
+
+    /// Change the timings and wiring of the button
+    pub const fn set_config(&mut self, config: ButtonConfig) {
+        self.input.set_settle_ms(config.settle_ms);
+        self.config = config;
+    }
+
+    /// Get whether the button is pressed, after debouncing
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn is_pressed(&mut self) -> Result<bool, Pcal6416aError<I2c::Error>> {
+        Ok(self.input.level().await? != self.config.active_low)
+    }
+
+    /// Wait for the next gesture
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn next_event(&mut self) -> Result<ButtonEvent, Pcal6416aError<I2c::Error>> {
+        if let Some(event) = self.pending.take() {
+            return Ok(event);
+        }
+
+        loop {
+            // A change seen by `is_pressed` calls in between is reported now
+            let level = self.is_pressed().await?;
+            let pressed = *self.pressed.get_or_insert(level);
+            let timeout = if pressed {
+                if self.long_pressed {
+                    self.config.repeat_ms
+                } else {
+                    Some(self.config.long_press_ms)
+                }
+            } else if self.click_pending {
+                Some(self.config.double_click_ms)
+            } else {
+                None
+            };
+
+            if level != pressed || self.wait_for_change(timeout).await? {
+                self.pressed = Some(!pressed);
+                if pressed {
+                    self.click_pending = !self.long_pressed && !self.second_click;
+                    self.long_pressed = false;
+                    self.second_click = false;
+                    return Ok(ButtonEvent::Release);
+                }
+
+                if core::mem::take(&mut self.click_pending) {
+                    self.second_click = true;
+                    self.pending = Some(ButtonEvent::DoubleClick);
+                }
+                return Ok(ButtonEvent::Press);
+            }
+
+            if pressed {
+                let event = if self.long_pressed {
+                    ButtonEvent::Repeat
+                } else {
+                    ButtonEvent::LongPress
+                };
+                self.long_pressed = true;
+                return Ok(event);
+            }
+
+            self.click_pending = false;
+        }
+    }
+
+    /// Release the pin and the delay provider
+    pub fn into_inner(self) -> (IoPin<'a, I2c, M, C>, D) {
+        (self.input.into_inner().0, self.timer)
+    }
+
+    /// Wait for the debounced level to change, or `timeout_ms` to elapse
+    ///
+    /// Returns whether the level changed.
+    async fn wait_for_change(&mut self, timeout_ms: Option<u32>) -> Result<bool, Pcal6416aError<I2c::Error>> {
+        let Some(timeout_ms) = timeout_ms else {
+            return self.input.wait_for_change().await.map(|_| true);
+        };
+
+        match with_timeout(self.input.wait_for_change(), self.timer.delay_ms(timeout_ms)).await {
+            Some(result) => result.map(|_| true),
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, Transaction as DelayTransaction};
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use super::*;
+    use crate::{AddrPinState, Device, Pcal6416aDevice, SharedDevice};
+
+    #[tokio::test]
+    async fn gestures() {
+        let read = |level: u8| Transaction::write_read(0x20, vec![0x00], vec![level]);
+        let i2cbus = Mock::new(&[
+            // Released, then pressed and held
+            read(0x01),
+            read(0x01),
+            read(0x00),
+            read(0x00),
+            read(0x00),
+            read(0x00),
+            read(0x00),
+            read(0x00),
+            read(0x00),
+            read(0x00),
+            read(0x00),
+            read(0x00),
+            read(0x01),
+            read(0x01),
+            // Double click
+            read(0x01),
+            read(0x01),
+            read(0x00),
+            read(0x00),
+            read(0x00),
+            read(0x00),
+            read(0x01),
+            read(0x01),
+            read(0x01),
+            read(0x01),
+            read(0x00),
+            read(0x00),
+        ]);
+        let settle = DelayTransaction::async_delay_ms(5);
+        let delay = CheckedDelay::new(&[
+            settle.clone(),
+            settle.clone(),
+            DelayTransaction::async_delay_ms(500),
+            DelayTransaction::async_delay_ms(100),
+            settle.clone(),
+            settle.clone(),
+            settle.clone(),
+            settle,
+        ]);
+        let dev: SharedDevice<_, NoopRawMutex> =
+            SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus)));
+
+        let [pin, ..] = dev.split();
+        let mut button = Button::new(pin, delay).with_config(ButtonConfig {
+            settle_ms: 5,
+            repeat_ms: Some(100),
+            ..ButtonConfig::default()
+        });
+
+        let mut events = Vec::new();
+        for _ in 0..8 {
+            events.push(button.next_event().await.unwrap());
+        }
+        assert_eq!(
+            events,
+            [
+                ButtonEvent::Press,
+                ButtonEvent::LongPress,
+                ButtonEvent::Repeat,
+                ButtonEvent::Release,
+                ButtonEvent::Press,
+                ButtonEvent::Release,
+                ButtonEvent::Press,
+                ButtonEvent::DoubleClick,
+            ]
+        );
+
+        let (_, mut delay) = button.into_inner();
+        delay.done();
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+}
@@ -0,0 +1,153 @@
+//! Chip descriptions for the NXP agile I/O expander family (PCAL6408A, PCAL6416A, PCAL6524).
+//!
+//! The PCAL6408A, PCAL6416A and PCAL6524 share the same register banks (input, output,
+//! configuration, pull resistors, interrupt mask/status, ...) and differ only in the
+//! number of 8-bit ports, where each bank lives and which I2C addresses they answer on.
+//! A [`Chip`] captures those differences so the pin-level API works across the family.
+
+use crate::{AddrPinState, Port};
+
+/// Register bank holding one byte per port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Bank {
+    /// Input port registers (read-only)
+    Input,
+    /// Output port registers
+    Output,
+    /// Polarity inversion registers
+    PolarityInversion,
+    /// Configuration (direction) registers, set bits are inputs
+    Configuration,
+    /// Input latch registers
+    InputLatch,
+    /// Pull-up/pull-down enable registers
+    PullEnable,
+    /// Pull-up/pull-down selection registers
+    PullSelect,
+    /// Interrupt mask registers, set bits are masked
+    InterruptMask,
+    /// Interrupt status registers (read-only)
+    InterruptStatus,
+}
+
+/// Description of an expander in the agile I/O family.
+pub trait Chip {
+    /// Number of 8-bit ports
+    const PORTS: usize;
+
+    /// Number of I/O pins
+    const PINS: usize = Self::PORTS * 8;
+
+    /// Bit set in the command byte to auto-increment across the ports of a bank
+    const AUTO_INCREMENT: u8 = 0;
+
+    /// Address of the Output Port Configuration register, one open-drain bit per port
+    const OUTPUT_CONFIG: u8 = 0x4F;
+
+    /// All I2C addresses the part can be strapped to
+    const ADDRESSES: &'static [u8];
+
+    /// I2C address selected by the state of the ADDR pin
+    fn address(addr_pin: AddrPinState) -> u8;
+
+    /// Register address of `bank` for `port`
+    ///
+    /// `port` must be below [`Chip::PORTS`].
+    fn register(bank: Bank, port: Port) -> u8;
+}
+
+/// NXP PCAL6416A, 16-bit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Pcal6416a;
+
+impl Chip for Pcal6416a {
+    const PORTS: usize = 2;
+    const ADDRESSES: &'static [u8] = &[0x20, 0x21];
+
+    fn address(addr_pin: AddrPinState) -> u8 {
+        addr_pin.address()
+    }
+
+    fn register(bank: Bank, port: Port) -> u8 {
+        let base = match bank {
+            Bank::Input => 0x00,
+            Bank::Output => 0x02,
+            Bank::PolarityInversion => 0x04,
+            Bank::Configuration => 0x06,
+            Bank::InputLatch => 0x44,
+            Bank::PullEnable => 0x46,
+            Bank::PullSelect => 0x48,
+            Bank::InterruptMask => 0x4A,
+            Bank::InterruptStatus => 0x4C,
+        };
+        base + port.index()
+    }
+}
+
+/// NXP PCAL6408A, 8-bit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Pcal6408a;
+
+impl Chip for Pcal6408a {
+    const PORTS: usize = 1;
+    const ADDRESSES: &'static [u8] = &[0x20, 0x21];
+
+    fn address(addr_pin: AddrPinState) -> u8 {
+        addr_pin.address()
+    }
+
+    fn register(bank: Bank, port: Port) -> u8 {
+        let base = match bank {
+            Bank::Input => 0x00,
+            Bank::Output => 0x01,
+            Bank::PolarityInversion => 0x02,
+            Bank::Configuration => 0x03,
+            Bank::InputLatch => 0x42,
+            Bank::PullEnable => 0x43,
+            Bank::PullSelect => 0x44,
+            Bank::InterruptMask => 0x45,
+            Bank::InterruptStatus => 0x46,
+        };
+        base + port.index()
+    }
+}
+
+/// NXP PCAL6524, 24-bit
+///
+/// The ADDR pin of this part can also be tied to SCL or SDA (0x20/0x21); [`AddrPinState`]
+/// selects between the VSS (0x22) and VDD (0x23) straps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Pcal6524;
+
+impl Chip for Pcal6524 {
+    const PORTS: usize = 3;
+    const AUTO_INCREMENT: u8 = 0x80;
+    const OUTPUT_CONFIG: u8 = 0x5C;
+    const ADDRESSES: &'static [u8] = &[0x20, 0x21, 0x22, 0x23];
+
+    fn address(addr_pin: AddrPinState) -> u8 {
+        match addr_pin {
+            AddrPinState::Low => 0x22,
+            AddrPinState::High => 0x23,
+        }
+    }
+
+    fn register(bank: Bank, port: Port) -> u8 {
+        let base = match bank {
+            Bank::Input => 0x00,
+            Bank::Output => 0x04,
+            Bank::PolarityInversion => 0x08,
+            Bank::Configuration => 0x0C,
+            Bank::InputLatch => 0x48,
+            Bank::PullEnable => 0x4C,
+            Bank::PullSelect => 0x50,
+            Bank::InterruptMask => 0x54,
+            Bank::InterruptStatus => 0x58,
+        };
+        base + port.index()
+    }
+}
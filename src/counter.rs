@@ -0,0 +1,137 @@
+//! Per-pin edge counters.
+//!
+//! The interrupt layer counts the rising and falling edges of every pin, including both
+//! edges of a reported pulse. Counting needs no setup; counters only advance while the
+//! device is serviced or polled.
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+
+use crate::interrupt::bits;
+use crate::{Chip, IoPin, Pin, Port, SharedDevice};
+
+/// Edges counted on a pin since the last reset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EdgeCount {
+    /// Rising edges
+    pub rising: u32,
+    /// Falling edges
+    pub falling: u32,
+}
+
+impl EdgeCount {
+    /// Complete pulses, i.e. the smaller of the two counts
+    #[must_use]
+    pub const fn pulses(&self) -> u32 {
+        if self.rising < self.falling {
+            self.rising
+        } else {
+            self.falling
+        }
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> SharedDevice<I2c, M, C> {
+    /// Get the edges counted on a pin since its last reset
+    ///
+    /// Counters wrap around on overflow.
+    #[must_use]
+    pub fn edge_count(&self, port: Port, pin: Pin) -> EdgeCount {
+        let index = usize::from(port.index() * 8 + pin.bit());
+        self.with_events(|events| {
+            let (rising, falling) = events.sequence(index);
+            let (rising_base, falling_base) = events.count_base[index];
+            EdgeCount {
+                rising: rising.wrapping_sub(rising_base),
+                falling: falling.wrapping_sub(falling_base),
+            }
+        })
+    }
+
+    /// Get and reset the edges counted on a pin, as one step
+    #[must_use]
+    pub fn take_edge_count(&self, port: Port, pin: Pin) -> EdgeCount {
+        let index = usize::from(port.index() * 8 + pin.bit());
+        self.with_events(|events| {
+            let (rising, falling) = events.sequence(index);
+            let (rising_base, falling_base) = core::mem::replace(&mut events.count_base[index], (rising, falling));
+            EdgeCount {
+                rising: rising.wrapping_sub(rising_base),
+                falling: falling.wrapping_sub(falling_base),
+            }
+        })
+    }
+
+    /// Reset the edge counters of the pins in `pins`, bit `port * 8 + pin`
+    pub fn reset_edge_counts(&self, pins: u32) {
+        self.with_events(|events| {
+            for index in bits(pins) {
+                events.count_base[index] = events.sequence(index);
+            }
+        });
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> IoPin<'_, I2c, M, C> {
+    /// Get the edges counted on this pin since its last reset
+    #[must_use]
+    pub fn edge_count(&self) -> EdgeCount {
+        self.shared.edge_count(self.port, self.pin)
+    }
+
+    /// Get and reset the edges counted on this pin, as one step
+    #[must_use]
+    pub fn take_edge_count(&self) -> EdgeCount {
+        self.shared.take_edge_count(self.port, self.pin)
+    }
+
+    /// Reset the edge counters of this pin
+    pub fn reset_edge_count(&self) {
+        self.shared.reset_edge_counts(1 << self.index());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use super::*;
+    use crate::{AddrPinState, Device, Pcal6416aDevice};
+
+    #[tokio::test]
+    async fn counts_edges_and_pulses() {
+        let mut expectations = vec![Transaction::write_read(0x20, vec![0x00], vec![0x00, 0x00])];
+        for (status, levels) in [(0x01, 0x01), (0x01, 0x00), (0x01, 0x00), (0x01, 0x01)] {
+            expectations.push(Transaction::write_read(0x20, vec![0x4C], vec![status, 0x00]));
+            expectations.push(Transaction::write_read(0x20, vec![0x00], vec![levels, 0x00]));
+        }
+        let dev: SharedDevice<_, NoopRawMutex> = SharedDevice::new(Device::new(Pcal6416aDevice::new(
+            AddrPinState::Low,
+            Mock::new(&expectations),
+        )));
+
+        dev.sample_inputs().await.unwrap();
+        let pins = dev.split();
+        for _ in 0..2 {
+            dev.service_interrupt().await.unwrap();
+        }
+        assert_eq!(pins[0].edge_count(), EdgeCount { rising: 1, falling: 1 });
+        assert_eq!(pins[1].edge_count(), EdgeCount::default());
+
+        // A pulse counts as both edges
+        dev.service_interrupt().await.unwrap();
+        assert_eq!(pins[0].take_edge_count(), EdgeCount { rising: 2, falling: 2 });
+        assert_eq!(pins[0].edge_count().pulses(), 0);
+
+        dev.service_interrupt().await.unwrap();
+        assert_eq!(
+            dev.edge_count(Port::Port0, Pin::Pin0),
+            EdgeCount { rising: 1, falling: 0 }
+        );
+        pins[0].reset_edge_count();
+        assert_eq!(pins[0].edge_count(), EdgeCount::default());
+
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+}
@@ -0,0 +1,255 @@
+//! Software debounce for expander inputs.
+//!
+//! [`Debounced`] wraps an [`IoPin`] and only reports a new level once the input has stayed
+//! unchanged for the settle time. Bounces are detected through the pin's edge counters, so
+//! the device has to be serviced or polled, e.g. by an [`crate::InterruptDispatcher`] or an
+//! [`crate::InputPoller`], for edges during the settle time to be noticed.
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embedded_hal_async::delay::DelayNs;
+
+use crate::{Chip, IoPin, Pcal6416aError};
+
+/// Default settle time, in milliseconds
+pub const DEFAULT_SETTLE_MS: u32 = 20;
+
+/// Input pin reporting its debounced level.
+///
+/// # Example
+/// ```ignore
+/// let pins = shared.split();
+/// let [button, ..] = pins;
+/// let mut button = Debounced::new(button, embassy_time::Delay).with_settle_ms(10);
+///
+/// loop {
+///     button.wait_for_falling_edge().await?;
+///     // pressed, once per press
+/// }
+/// ```
+pub struct Debounced<'a, D: DelayNs, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> {
+    pin: IoPin<'a, I2c, M, C>,
+    delay: D,
+    settle_ms: u32,
+    /// Last debounced level, `None` until first settled
+    stable: Option<bool>,
+}
+
+impl<'a, D: DelayNs, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> Debounced<'a, D, I2c, M, C> {
+    /// Debounce `pin`, timing the settle time with `delay`
+    pub const fn new(pin: IoPin<'a, I2c, M, C>, delay: D) -> Self {
+        Self {
+            pin,
+            delay,
+            settle_ms: DEFAULT_SETTLE_MS,
+            stable: None,
+        }
+    }
+
+    /// Set the time the input has to stay unchanged
+    #[must_use]
+    pub const fn with_settle_ms(mut self, settle_ms: u32) -> Self {
+        self.settle_ms = settle_ms;
+        self
+    }
+
+    /// Get the time the input has to stay unchanged, in milliseconds
+    #[must_use]
+    pub const fn settle_ms(&self) -> u32 {
+        self.settle_ms
+    }
+
+    /// Change the time the input has to stay unchanged
+    pub const fn set_settle_ms(&mut self, settle_ms: u32) {
+        self.settle_ms = settle_ms;
+    }
+
+    /// Get the debounced level
+    ///
+    /// The input is sampled on every call; while it matches the last debounced level
+    /// that level is returned at once, otherwise the input is left to settle first.
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn level(&mut self) -> Result<bool, Pcal6416aError<I2c::Error>> {
+        if let Some(stable) = self.stable
+            && self.pin.is_high_async().await? == stable
+        {
+            return Ok(stable);
+        }
+
+        let level = self.settle().await?;
+        self.stable = Some(level);
+        Ok(level)
+    }
+
+    /// Wait for the debounced level to change, returning the new level
+    ///
+    /// Returns at once if the level changed since it was last reported.
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn wait_for_change(&mut self) -> Result<bool, Pcal6416aError<I2c::Error>> {
+        let reported = self.stable;
+        let stable = self.level().await?;
+        if reported.is_some_and(|reported| reported != stable) {
+            return Ok(stable);
+        }
+
+        loop {
+            self.pin.wait_for((true, true), Some(!stable)).await?;
+            let level = self.settle().await?;
+            if level != stable {
+                self.stable = Some(level);
+                return Ok(level);
+            }
+        }
+    }
+
+    /// Release the pin and the delay provider
+    pub fn into_inner(self) -> (IoPin<'a, I2c, M, C>, D) {
+        (self.pin, self.delay)
+    }
+
+    /// Wait until no edge is seen on the pin for the settle time, returning its level
+    async fn settle(&mut self) -> Result<bool, Pcal6416aError<I2c::Error>> {
+        loop {
+            let before = self.edges();
+            self.delay.delay_ms(self.settle_ms).await;
+            // The read itself reports an edge if the level moved unnoticed
+            let level = self.pin.is_high_async().await?;
+            if self.edges() == before {
+                return Ok(level);
+            }
+        }
+    }
+
+    /// Total edges counted on the pin
+    fn edges(&self) -> u32 {
+        let count = self.pin.edge_count();
+        count.rising.wrapping_add(count.falling)
+    }
+}
+
+impl<D: DelayNs, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> embedded_hal::digital::ErrorType
+    for Debounced<'_, D, I2c, M, C>
+{
+    type Error = Pcal6416aError<I2c::Error>;
+}
+
+impl<D: DelayNs, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> embedded_hal_async::digital::InputPin
+    for Debounced<'_, D, I2c, M, C>
+{
+    async fn is_high(&mut self) -> Result<bool, Self::Error> {
+        self.level().await
+    }
+
+    async fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.level().await?)
+    }
+}
+
+impl<D: DelayNs, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> embedded_hal_async::digital::Wait
+    for Debounced<'_, D, I2c, M, C>
+{
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        while !self.level().await? {
+            self.wait_for_change().await?;
+        }
+        Ok(())
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        while self.level().await? {
+            self.wait_for_change().await?;
+        }
+        Ok(())
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        while !self.wait_for_change().await? {}
+        Ok(())
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        while self.wait_for_change().await? {}
+        Ok(())
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_change().await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embedded_hal_async::digital::{InputPin, Wait};
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, Transaction as DelayTransaction};
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use super::*;
+    use crate::{AddrPinState, Device, Pcal6416aDevice, SharedDevice};
+
+    #[tokio::test]
+    async fn bounces_are_ignored() {
+        let i2cbus = Mock::new(&[
+            // Initial settle, then sampled again before waiting
+            Transaction::write_read(0x20, vec![0x00], vec![0x01]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x01]),
+            // Press, bouncing once before settling low
+            Transaction::write_read(0x20, vec![0x00], vec![0x00]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x01]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x00]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x00]),
+            // Still low when read back
+            Transaction::write_read(0x20, vec![0x00], vec![0x00]),
+        ]);
+        let delay = CheckedDelay::new(&[
+            DelayTransaction::async_delay_ms(5),
+            DelayTransaction::async_delay_ms(5),
+            DelayTransaction::async_delay_ms(5),
+            DelayTransaction::async_delay_ms(5),
+        ]);
+        let dev: SharedDevice<_, NoopRawMutex> =
+            SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus)));
+
+        let [pin, ..] = dev.split();
+        let mut button = Debounced::new(pin, delay).with_settle_ms(5);
+        assert!(button.is_high().await.unwrap());
+        button.wait_for_falling_edge().await.unwrap();
+        assert!(button.is_low().await.unwrap());
+
+        let (_, mut delay) = button.into_inner();
+        delay.done();
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+
+    #[tokio::test]
+    async fn polled_level_follows_the_input() {
+        let read = |level: u8| Transaction::write_read(0x20, vec![0x00], vec![level]);
+        let i2cbus = Mock::new(&[
+            // Settled high, then polled high
+            read(0x01),
+            read(0x01),
+            // Polled low, settling low
+            read(0x00),
+            read(0x00),
+            // Polled low again without settling
+            read(0x00),
+        ]);
+        let delay = CheckedDelay::new(&[DelayTransaction::async_delay_ms(5), DelayTransaction::async_delay_ms(5)]);
+        let dev: SharedDevice<_, NoopRawMutex> =
+            SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus)));
+
+        let [pin, ..] = dev.split();
+        let mut input = Debounced::new(pin, delay).with_settle_ms(5);
+        assert!(input.is_high().await.unwrap());
+        assert!(input.is_high().await.unwrap());
+        assert!(input.is_low().await.unwrap());
+        assert!(input.is_low().await.unwrap());
+
+        let (_, mut delay) = input.into_inner();
+        delay.done();
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+}
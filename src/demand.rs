@@ -0,0 +1,232 @@
+//! Demand-driven interrupt masking.
+//!
+//! With demand masking enabled the driver owns the Interrupt Mask registers: a pin is
+//! unmasked while at least one task waits on it through [`embedded_hal_async::digital::Wait`]
+//! or [`SharedDevice::wait_for_any`] and masked otherwise, so the host is only woken for
+//! pins someone cares about. Pins can also request their input latch to be enabled while
+//! waited on, so short pulses are held until read.
+//!
+//! Mask and latch changes are computed from the waiter counts and written in one bank
+//! access each, under the [`SharedDevice`] mutex, the next time the device is sampled.
+//! A wait that completes re-masks its pin before returning; a cancelled wait leaves it
+//! unmasked until the next access.
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+
+use crate::interrupt::{MAX_PINS, bits};
+use crate::{Bank, Chip, Device, IoPin, Pcal6416aDevice, Pcal6416aError, SharedDevice};
+
+/// Waiter bookkeeping for demand masking
+pub(crate) struct DemandTracker {
+    enabled: bool,
+    /// Tasks waiting per pin
+    waiters: [u8; MAX_PINS],
+    /// Pins asking for their input latch while waited on
+    latch_requested: u32,
+    /// Interrupt Mask value last written, `None` when unknown
+    applied_mask: Option<u32>,
+    /// Input latch bits set on behalf of waiters
+    latched: u32,
+}
+
+impl DemandTracker {
+    pub(crate) const fn new() -> Self {
+        Self {
+            enabled: false,
+            waiters: [0; MAX_PINS],
+            latch_requested: 0,
+            applied_mask: None,
+            latched: 0,
+        }
+    }
+
+    /// Pins with at least one waiter
+    fn waiting(&self) -> u32 {
+        self.waiters
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .fold(0, |pins, (index, _)| pins | 1 << index)
+    }
+
+    /// Register values to write, if they differ from those applied
+    ///
+    /// `all` covers the pins of the chip, `blocked` the pins that stay masked regardless.
+    fn pending(&self, all: u32, blocked: u32) -> Option<(Option<u32>, u32)> {
+        if !self.enabled {
+            return None;
+        }
+
+        let waiting = self.waiting();
+        let mask = all & !(waiting & !blocked);
+        let latched = waiting & self.latch_requested;
+        let mask = (self.applied_mask != Some(mask)).then_some(mask);
+        (mask.is_some() || latched != self.latched).then_some((mask, latched))
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> SharedDevice<I2c, M, C> {
+    /// Enable or disable demand-driven interrupt masking
+    ///
+    /// While enabled, the Interrupt Mask registers are managed by the driver and pins are
+    /// unmasked only while waited on. Disabling leaves the registers as they are.
+    pub fn set_demand_masking(&self, enabled: bool) {
+        self.with_events(|events| {
+            events.demand.enabled = enabled;
+            events.demand.applied_mask = None;
+        });
+    }
+
+    /// Request the input latch of the pins in `pins`, bit `port * 8 + pin`, to be
+    /// enabled while they are waited on
+    ///
+    /// Only applies with demand masking enabled.
+    pub fn set_wait_latch(&self, pins: u32, latch: bool) {
+        self.with_events(|events| {
+            let requested = &mut events.demand.latch_requested;
+            *requested = if latch { *requested | pins } else { *requested & !pins };
+        });
+    }
+
+    /// Bring the Interrupt Mask and input latch registers in line with the current waiters
+    pub(crate) async fn sync_demand(
+        &self,
+        device: &mut Device<Pcal6416aDevice<I2c, C>>,
+    ) -> Result<(), Pcal6416aError<I2c::Error>> {
+        if !device.interface.variant.has_agile_io() {
+            return Ok(());
+        }
+
+        let all = crate::interrupt::all_pins::<C>();
+        let Some((mask, latched)) = self.with_events(|events| events.demand.pending(all, events.storm.tripped()))
+        else {
+            return Ok(());
+        };
+
+        if let Some(mask) = mask {
+            device.write_bank_async(Bank::InterruptMask, mask).await?;
+        }
+
+        let previous = self.with_events(|events| events.demand.latched);
+        if latched != previous {
+            let current = device.read_bank_async(Bank::InputLatch).await?;
+            device
+                .write_bank_async(Bank::InputLatch, (current & !(previous & !latched)) | latched)
+                .await?;
+        }
+
+        self.with_events(|events| {
+            if mask.is_some() {
+                events.demand.applied_mask = mask;
+            }
+            events.demand.latched = latched;
+        });
+        Ok(())
+    }
+
+    /// Whether demand masking is enabled
+    pub(crate) fn demand_masking(&self) -> bool {
+        self.with_events(|events| events.demand.enabled)
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> IoPin<'_, I2c, M, C> {
+    /// Request the input latch of this pin while waited on, see [`SharedDevice::set_wait_latch`]
+    pub fn set_wait_latch(&self, latch: bool) {
+        self.shared.set_wait_latch(1 << self.index(), latch);
+    }
+}
+
+/// Waiter registration on a set of pins, released on drop
+pub(crate) struct Waiter<'a, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> {
+    shared: &'a SharedDevice<I2c, M, C>,
+    pins: u32,
+}
+
+impl<'a, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> Waiter<'a, I2c, M, C> {
+    pub(crate) fn new(shared: &'a SharedDevice<I2c, M, C>, pins: u32) -> Self {
+        shared.with_events(|events| {
+            for index in bits(pins) {
+                events.demand.waiters[index] = events.demand.waiters[index].saturating_add(1);
+            }
+        });
+        Self { shared, pins }
+    }
+
+    /// Release the registration, re-masking pins left without waiters
+    pub(crate) async fn release(self) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let shared = self.shared;
+        drop(self);
+        if shared.demand_masking() {
+            let mut device = shared.device.lock().await;
+            shared.sync_demand(&mut device).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> Drop for Waiter<'_, I2c, M, C> {
+    fn drop(&mut self) {
+        self.shared.with_events(|events| {
+            for index in bits(self.pins) {
+                events.demand.waiters[index] = events.demand.waiters[index].saturating_sub(1);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::future::poll_fn;
+    use core::task::Poll;
+
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embedded_hal_async::digital::Wait;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use super::*;
+    use crate::AddrPinState;
+
+    #[tokio::test]
+    async fn waiters_unmask_their_pins() {
+        let i2cbus = Mock::new(&[
+            // Waiter on pin 0_2 with latch
+            Transaction::write(0x20, vec![0x4A, 0xFB, 0xFF]),
+            Transaction::write_read(0x20, vec![0x44], vec![0x00, 0x00]),
+            Transaction::write(0x20, vec![0x44, 0x04, 0x00]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x04]),
+            // Second waiter on pin 1_0, batched into one mask write
+            Transaction::write(0x20, vec![0x4A, 0xFB, 0xFE]),
+            Transaction::write_read(0x20, vec![0x01], vec![0x00]),
+            Transaction::write_read(0x20, vec![0x4C], vec![0x04, 0x00]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x00, 0x00]),
+            // First wait done
+            Transaction::write(0x20, vec![0x4A, 0xFF, 0xFE]),
+            Transaction::write_read(0x20, vec![0x44], vec![0x04, 0x00]),
+            Transaction::write(0x20, vec![0x44, 0x00, 0x00]),
+            // Second wait cancelled, re-masked on next access
+            Transaction::write(0x20, vec![0x4A, 0xFF, 0xFF]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x00, 0x00]),
+        ]);
+        let dev: SharedDevice<_, NoopRawMutex> =
+            SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus)));
+        dev.set_demand_masking(true);
+
+        {
+            let mut pins = dev.split();
+            pins[2].set_wait_latch(true);
+            let (left, right) = pins.split_at_mut(8);
+            let mut first = core::pin::pin!(left[2].wait_for_falling_edge());
+            let mut second = core::pin::pin!(right[0].wait_for_high());
+            assert!(poll_fn(|cx| Poll::Ready(first.as_mut().poll(cx))).await.is_pending());
+            assert!(poll_fn(|cx| Poll::Ready(second.as_mut().poll(cx))).await.is_pending());
+
+            dev.service_interrupt().await.unwrap();
+            assert!(poll_fn(|cx| Poll::Ready(first.as_mut().poll(cx))).await.is_ready());
+        }
+
+        dev.sample_inputs().await.unwrap();
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+}
@@ -0,0 +1,292 @@
+//! Quadrature rotary encoder decoding.
+//!
+//! [`Encoder`] follows the A and B outputs of an encoder wired to two inputs of a
+//! [`SharedDevice`]. It consumes the pin events published for those inputs, with their
+//! input latches enabled so short states are held until the expander is read, and tracks
+//! the two-bit state of both pins rather than counting edges of one: the events of an input
+//! sample are decoded together, and each change of state moves the position one step in
+//! the direction given by the Gray code sequence. A sample finding both pins changed
+//! missed the state in between; it counts as two steps in the direction of the last one.
+//! Events are queued, so the decoder tolerates the latency of the I2C reads as long as it
+//! keeps within [`crate::EVENT_QUEUE_DEPTH`] events.
+//!
+//! The device has to be serviced, e.g. by an [`crate::InterruptDispatcher`].
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+
+use crate::demand::Waiter;
+use crate::{Bank, Chip, EventKind, IoPin, Pcal6416aError, PinEvent, PinEvents, SharedDevice};
+
+/// Default number of steps between two detents
+pub const DEFAULT_STEPS_PER_DETENT: u8 = 4;
+
+/// Direction of rotation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Direction {
+    /// (A, B) going 00, 01, 11, 10
+    Forward,
+    /// (A, B) going 00, 10, 11, 01
+    Backward,
+}
+
+/// Quadrature encoder on two pins of a [`SharedDevice`].
+///
+/// # Example
+/// ```ignore
+/// let pins = shared.split();
+/// let mut encoder = Encoder::new(&pins[4], &pins[5]).unwrap();
+/// encoder.init().await?;
+///
+/// loop {
+///     encoder.next_step().await;
+///     set_volume(encoder.detents());
+/// }
+/// ```
+pub struct Encoder<'a, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> {
+    shared: &'a SharedDevice<I2c, M, C>,
+    events: PinEvents<'a, M>,
+    /// Keeps the pins unmasked and latched under demand masking
+    _waiter: Waiter<'a, I2c, M, C>,
+    a: u8,
+    b: u8,
+    /// Levels of A (bit 1) and B (bit 0)
+    state: u8,
+    /// Event of the next sample, taken from the queue while decoding the previous one
+    held: Option<PinEvent>,
+    position: i32,
+    direction: Option<Direction>,
+    steps_per_detent: u8,
+}
+
+impl<'a, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> Encoder<'a, I2c, M, C> {
+    /// Create a decoder for an encoder with outputs A on `a` and B on `b`
+    ///
+    /// Returns `None` if the device has no event subscription left, see
+    /// [`SharedDevice::subscribe`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `a` and `b` belong to different devices.
+    #[must_use]
+    pub fn new(a: &IoPin<'a, I2c, M, C>, b: &IoPin<'a, I2c, M, C>) -> Option<Self> {
+        assert!(
+            core::ptr::eq(a.shared, b.shared),
+            "encoder pins must be on the same device"
+        );
+        let shared = a.shared;
+        let pins = 1 << a.index() | 1 << b.index();
+        Some(Self {
+            shared,
+            events: shared.subscribe(pins)?,
+            _waiter: Waiter::new(shared, pins),
+            a: a.index(),
+            b: b.index(),
+            state: 0,
+            held: None,
+            position: 0,
+            direction: None,
+            steps_per_detent: DEFAULT_STEPS_PER_DETENT,
+        })
+    }
+
+    /// Set the number of steps between two detents, see [`Encoder::detents`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if `steps_per_detent` is zero.
+    #[must_use]
+    pub fn with_steps_per_detent(mut self, steps_per_detent: u8) -> Self {
+        assert!(steps_per_detent > 0, "steps_per_detent must not be zero");
+        self.steps_per_detent = steps_per_detent;
+        self
+    }
+
+    /// Enable the input latch and interrupt of both pins and sample their state
+    ///
+    /// With demand masking enabled the pins are latched and unmasked through it for as
+    /// long as the encoder exists.
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn init(&mut self) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let pins = 1 << self.a | 1 << self.b;
+        if self.shared.demand_masking() {
+            self.shared.set_wait_latch(pins, true);
+        } else {
+            let mut device = self.shared.device.lock().await;
+            if device.interface.variant.has_agile_io() {
+                let latch = device.read_bank_async(Bank::InputLatch).await?;
+                device.write_bank_async(Bank::InputLatch, latch | pins).await?;
+                let mask = device.read_bank_async(Bank::InterruptMask).await?;
+                device.write_bank_async(Bank::InterruptMask, mask & !pins).await?;
+            }
+        }
+
+        let levels = self.shared.sample_inputs().await?.levels;
+        // The sample already holds whatever it reported
+        while self.events.try_next().is_some() {}
+        self.held = None;
+        self.state = u8::from(levels & 1 << self.a != 0) << 1 | u8::from(levels & 1 << self.b != 0);
+        Ok(())
+    }
+
+    /// Wait for the encoder to move, returning the steps taken (positive forward)
+    pub async fn next_step(&mut self) -> i32 {
+        loop {
+            let event = match self.held.take() {
+                Some(event) => event,
+                None => self.events.next().await,
+            };
+            let steps = self.apply(event);
+            if steps != 0 {
+                return steps;
+            }
+        }
+    }
+
+    /// Apply the buffered events without waiting, returning the steps taken
+    pub fn poll(&mut self) -> i32 {
+        let mut steps = 0;
+        while let Some(event) = self.held.take().or_else(|| self.events.try_next()) {
+            steps += self.apply(event);
+        }
+        steps
+    }
+
+    /// Get the position in steps since creation or the last [`Encoder::set_position`]
+    #[must_use]
+    pub const fn position(&self) -> i32 {
+        self.position
+    }
+
+    /// Get the position in detents, rounded towards zero
+    #[must_use]
+    pub fn detents(&self) -> i32 {
+        self.position / i32::from(self.steps_per_detent)
+    }
+
+    /// Change the position
+    pub const fn set_position(&mut self, position: i32) {
+        self.position = position;
+    }
+
+    /// Get the direction of the last step, `None` before the first one
+    #[must_use]
+    pub const fn direction(&self) -> Option<Direction> {
+        self.direction
+    }
+
+    /// Update the state with the events of the sample of `event`, returning the steps taken
+    ///
+    /// The events of a sample are published together, so they are all queued by the time
+    /// the first one is received. Pulses leave the state unchanged, so they are ignored. A
+    /// sample matching the known state, which can follow events lost to a full queue, only
+    /// resynchronises it; one two steps away counts in the last direction, or not at all
+    /// before the first step.
+    fn apply(&mut self, event: PinEvent) -> i32 {
+        let sample = event.sample;
+        let mut state = self.state;
+        let mut next = Some(event);
+        while let Some(event) = next {
+            if event.sample != sample {
+                self.held = Some(event);
+                break;
+            }
+            if event.kind != EventKind::Pulse {
+                let bit = if event.index() == self.a { 0b10 } else { 0b01 };
+                state = if event.level { state | bit } else { state & !bit };
+            }
+            next = self.events.try_next();
+        }
+
+        let steps = match (gray_index(state) + 4 - gray_index(self.state)) % 4 {
+            1 => 1,
+            2 => match self.direction {
+                Some(Direction::Forward) => 2,
+                Some(Direction::Backward) => -2,
+                None => 0,
+            },
+            3 => -1,
+            _ => 0,
+        };
+        self.state = state;
+
+        if steps != 0 {
+            self.position = self.position.wrapping_add(steps);
+            self.direction = Some(if steps > 0 {
+                Direction::Forward
+            } else {
+                Direction::Backward
+            });
+        }
+        steps
+    }
+}
+
+/// Position of the (A, B) state `state` in the forward Gray code sequence
+const fn gray_index(state: u8) -> u8 {
+    match state & 0b11 {
+        0b00 => 0,
+        0b01 => 1,
+        0b11 => 2,
+        _ => 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use super::*;
+    use crate::{AddrPinState, Device, Pcal6416aDevice};
+
+    #[tokio::test]
+    async fn decodes_both_directions() {
+        let mut expectations = vec![
+            Transaction::write_read(0x20, vec![0x44], vec![0x00, 0x00]),
+            Transaction::write(0x20, vec![0x44, 0x03, 0x00]),
+            Transaction::write_read(0x20, vec![0x4A], vec![0xFF, 0xFF]),
+            Transaction::write(0x20, vec![0x4A, 0xFC, 0xFF]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x00, 0x00]),
+        ];
+        // A on pin 0_0, B on pin 0_1: forward, a pulse on A, forward, backward, then both
+        // pins changed at once
+        for (status, levels) in [
+            (0x02, 0x02),
+            (0x01, 0x03),
+            (0x02, 0x01),
+            (0x01, 0x01),
+            (0x01, 0x00),
+            (0x01, 0x01),
+            (0x03, 0x02),
+        ] {
+            expectations.push(Transaction::write_read(0x20, vec![0x4C], vec![status, 0x00]));
+            expectations.push(Transaction::write_read(0x20, vec![0x00], vec![levels, 0x00]));
+        }
+        let dev: SharedDevice<_, NoopRawMutex> = SharedDevice::new(Device::new(Pcal6416aDevice::new(
+            AddrPinState::Low,
+            Mock::new(&expectations),
+        )));
+
+        let pins = dev.split();
+        let mut encoder = Encoder::new(&pins[0], &pins[1]).unwrap();
+        encoder.init().await.unwrap();
+
+        for _ in 0..6 {
+            dev.service_interrupt().await.unwrap();
+        }
+        assert_eq!(encoder.next_step().await, 1);
+        assert_eq!(encoder.poll(), 2);
+        assert_eq!(encoder.position(), 3);
+        assert_eq!(encoder.direction(), Some(Direction::Backward));
+        assert_eq!(encoder.detents(), 0);
+
+        dev.service_interrupt().await.unwrap();
+        assert_eq!(encoder.poll(), -2);
+        assert_eq!(encoder.position(), 1);
+
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+}
@@ -0,0 +1,214 @@
+//! Async stream of pin events.
+//!
+//! Every edge found by the interrupt layer is published on a per-device
+//! [`PubSubChannel`]. Tasks subscribe with [`SharedDevice::subscribe`] to the pins they
+//! care about instead of polling them.
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::pubsub::{PubSubChannel, Subscriber};
+
+use crate::interrupt::Changes;
+use crate::{Chip, Edge, Pin, Port, SharedDevice};
+
+/// Number of events buffered per device before slow subscribers lose the oldest ones
+pub const EVENT_QUEUE_DEPTH: usize = 16;
+
+/// Maximum number of concurrent event subscribers per device
+pub const EVENT_SUBSCRIBERS: usize = 4;
+
+pub(crate) type EventChannel<M> = PubSubChannel<M, PinEvent, EVENT_QUEUE_DEPTH, EVENT_SUBSCRIBERS, 0>;
+
+/// What a [`PinEvent`] reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EventKind {
+    /// The input changed
+    Edge,
+    /// The input toggled twice between two samples and is back at its previous level;
+    /// `edge` is the first transition
+    Pulse,
+    /// The pin exceeded the storm protection limit and was masked, see
+    /// [`SharedDevice::set_storm_protection`]; `edge` is the transition that tripped it
+    Storm,
+}
+
+/// An input transition on one pin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PinEvent {
+    /// What the event reports
+    pub kind: EventKind,
+    /// Port of the pin
+    pub port: Port,
+    /// Pin within the port
+    pub pin: Pin,
+    /// Direction of the transition
+    pub edge: Edge,
+    /// Input level after the transition
+    pub level: bool,
+    /// Sequence number of the input sample that found the transition, shared by the
+    /// events of all pins changed in that sample
+    pub sample: u32,
+}
+
+impl PinEvent {
+    /// Get the index of the pin on its device (`port * 8 + pin`)
+    #[must_use]
+    pub const fn index(&self) -> u8 {
+        self.port.index() * 8 + self.pin.bit()
+    }
+}
+
+/// Subscription to the events of a subset of the pins of a [`SharedDevice`]
+pub struct PinEvents<'a, M: RawMutex> {
+    subscriber: Subscriber<'a, M, PinEvent, EVENT_QUEUE_DEPTH, EVENT_SUBSCRIBERS, 0>,
+    pins: u32,
+}
+
+impl<M: RawMutex> PinEvents<'_, M> {
+    /// Wait for the next event on one of the subscribed pins
+    ///
+    /// Events dropped because this subscriber fell more than [`EVENT_QUEUE_DEPTH`]
+    /// events behind are skipped.
+    pub async fn next(&mut self) -> PinEvent {
+        loop {
+            let event = self.subscriber.next_message_pure().await;
+            if self.pins & (1 << event.index()) != 0 {
+                return event;
+            }
+        }
+    }
+
+    /// Get the next buffered event on one of the subscribed pins, if any
+    pub fn try_next(&mut self) -> Option<PinEvent> {
+        while let Some(event) = self.subscriber.try_next_message_pure() {
+            if self.pins & (1 << event.index()) != 0 {
+                return Some(event);
+            }
+        }
+
+        None
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> SharedDevice<I2c, M, C> {
+    /// Subscribe to the events of the pins in `pins`, bit `port * 8 + pin`
+    ///
+    /// Events are produced by whatever samples the inputs: an
+    /// [`crate::InterruptDispatcher`], [`SharedDevice::service_interrupt`] or pin reads.
+    /// Returns `None` if [`EVENT_SUBSCRIBERS`] subscriptions are already active.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let pins = shared.split();
+    /// let mut buttons = shared.subscribe(1 << pins[0].index() | 1 << pins[1].index()).unwrap();
+    ///
+    /// loop {
+    ///     let event = buttons.next().await;
+    ///     if event.edge == Edge::Falling { /* pressed */ }
+    /// }
+    /// ```
+    #[must_use]
+    pub fn subscribe(&self, pins: u32) -> Option<PinEvents<'_, M>> {
+        Some(PinEvents {
+            subscriber: self.channel.subscriber().ok()?,
+            pins,
+        })
+    }
+
+    /// Publish the events found in a sample
+    #[allow(clippy::cast_possible_truncation)] // at most 24 pins
+    pub(crate) fn publish(&self, changes: &Changes) {
+        let publisher = self.channel.immediate_publisher();
+        for index in 0..C::PINS {
+            let bit = 1 << index;
+            let kind = if changes.stormed & bit != 0 {
+                EventKind::Storm
+            } else if changes.pulsed & bit != 0 {
+                EventKind::Pulse
+            } else if (changes.rising | changes.falling) & bit != 0 {
+                EventKind::Edge
+            } else {
+                continue;
+            };
+            let level = changes.levels & bit != 0;
+            // A pulse starts away from the level it returns to
+            let edge = if level == (kind == EventKind::Pulse) {
+                Edge::Falling
+            } else {
+                Edge::Rising
+            };
+
+            if let (Some(port), Some(pin)) = (Port::from_index((index / 8) as u8), Pin::from_bit((index % 8) as u8)) {
+                publisher.publish_immediate(PinEvent {
+                    kind,
+                    port,
+                    pin,
+                    edge,
+                    level,
+                    sample: changes.sample,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use super::*;
+    use crate::{AddrPinState, Device, Pcal6416aDevice};
+
+    #[tokio::test]
+    async fn subscribers_see_their_pins() {
+        let i2cbus = Mock::new(&[
+            Transaction::write_read(0x20, vec![0x00], vec![0x00]),
+            Transaction::write_read(0x20, vec![0x01], vec![0x00]),
+            Transaction::write_read(0x20, vec![0x4C], vec![0x03, 0x80]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x01, 0x80]),
+        ]);
+        let dev: SharedDevice<_, NoopRawMutex> =
+            SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus)));
+
+        let mut ui = dev.subscribe(0x0001).unwrap();
+        let mut power = dev.subscribe(0x8000).unwrap();
+
+        // Seed the snapshot
+        let pins = dev.split();
+        assert!(pins[0].is_low_async().await.unwrap());
+        assert!(pins[15].is_low_async().await.unwrap());
+
+        assert_eq!(dev.service_interrupt().await.unwrap(), 0x8003);
+        assert_eq!(
+            ui.next().await,
+            PinEvent {
+                kind: EventKind::Edge,
+                port: Port::Port0,
+                pin: Pin::Pin0,
+                edge: Edge::Rising,
+                level: true,
+                sample: 3
+            }
+        );
+        assert!(ui.try_next().is_none());
+        assert_eq!(power.next().await.index(), 15);
+        assert!(power.try_next().is_none());
+
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+
+    #[tokio::test]
+    async fn subscriber_limit() {
+        let dev: SharedDevice<_, NoopRawMutex> =
+            SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, Mock::new(&[]))));
+
+        let subscribers: [_; EVENT_SUBSCRIBERS] = core::array::from_fn(|_| dev.subscribe(u32::MAX).unwrap());
+        assert!(dev.subscribe(u32::MAX).is_none());
+        drop(subscribers);
+        assert!(dev.subscribe(u32::MAX).is_some());
+
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+}
@@ -0,0 +1,568 @@
+//! Fail-safe output state.
+//!
+//! A [`SafeState`] set on a [`Pcal6416aDevice`] gives the level, or high impedance, each
+//! covered pin falls back to: heaters and motor enables off, for instance. It is written
+//! on request and after the configured number of consecutive failed bus transfers. A
+//! [`SafeStateGuard`] holding a blocking I2C bus also writes it when dropped, as dropping
+//! cannot wait for an async bus.
+//!
+//! The safe state is written straight to the Output and Configuration registers, without
+//! going through the [`crate::Interlock`]s or pin locks: it is checked against both when
+//! set. Pins locked afterwards are left out when it is written, and so is the
+//! Configuration once its bank is locked or the device frozen. Only the covered pins change;
+//! when they are all covered, the registers are written without being read first, which
+//! improves the odds of getting through a faulty bus.
+//!
+//! High-impedance pins are made inputs before any Output bit changes and keep their Output
+//! bit, so they never glitch. Without a read, the current directions are unknown, so every
+//! pin is briefly made an input before the outputs are driven.
+
+use core::marker::PhantomData;
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+
+use crate::interrupt::all_pins;
+use crate::{
+    Bank, Chip, Device, Interlock, LARGEST_REG_SIZE_BYTES, Pcal6416a, Pcal6416aDevice, Pcal6416aError, Pin, Port,
+    SharedDevice, bank_register,
+};
+
+/// Fallback of a pin in a [`SafeState`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SafeLevel {
+    /// Output driven low
+    Low,
+    /// Output driven high
+    High,
+    /// Input, high impedance apart from the pull resistor
+    HighZ,
+}
+
+/// Fallback state of the outputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SafeState {
+    /// Covered pins, bit `port * 8 + pin`
+    pins: u32,
+    /// Covered pins driven high
+    high: u32,
+    /// Covered pins made inputs
+    high_z: u32,
+}
+
+impl SafeState {
+    /// Create a safe state covering no pin
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            pins: 0,
+            high: 0,
+            high_z: 0,
+        }
+    }
+
+    /// Cover a pin, falling back to `level`
+    #[must_use]
+    pub const fn with_pin(self, port: Port, pin: Pin, level: SafeLevel) -> Self {
+        self.with_pins(1 << (port.index() * 8 + pin.bit()), level)
+    }
+
+    /// Cover `pins`, bit `port * 8 + pin`, all falling back to `level`
+    #[must_use]
+    pub const fn with_pins(mut self, pins: u32, level: SafeLevel) -> Self {
+        self.pins |= pins;
+        self.high &= !pins;
+        self.high_z &= !pins;
+        match level {
+            SafeLevel::Low => {}
+            SafeLevel::High => self.high |= pins,
+            SafeLevel::HighZ => self.high_z |= pins,
+        }
+        self
+    }
+
+    /// Get the covered pins, bit `port * 8 + pin`
+    #[must_use]
+    pub const fn pins(&self) -> u32 {
+        self.pins
+    }
+
+    /// Whether applying the state leaves at most one pin of `interlock` high
+    ///
+    /// A covered pin driven high needs every other pin of the group covered and driven
+    /// low, as the others keep their levels, high-impedance pins included.
+    pub(crate) const fn satisfies(self, interlock: &Interlock) -> bool {
+        let high = self.high & interlock.pins;
+        let low = self.pins & !self.high & !self.high_z;
+        high == 0 || (high.is_power_of_two() && interlock.pins & !high & !low == 0)
+    }
+
+    /// The state limited to the Output bits of `pins`, and to the pins it keeps driven
+    /// unless `configure` allows writing the Configuration
+    const fn restrict(self, pins: u32, configure: bool) -> Self {
+        let pins = if configure { pins } else { pins & !self.high_z };
+        Self {
+            pins: self.pins & pins,
+            high: self.high & pins,
+            high_z: self.high_z & pins,
+        }
+    }
+
+    /// Bank values applying the state over `output` and `config`: the Configuration
+    /// releasing the high-impedance pins first, if needed, then the Output and the
+    /// Configuration
+    ///
+    /// With `blind` set, `output` and `config` are unknown and every pin is released.
+    const fn apply(self, output: u32, config: u32, blind: bool) -> (Option<u32>, u32, u32) {
+        let release = if self.high_z == 0 {
+            None
+        } else if blind {
+            Some(u32::MAX)
+        } else if self.high_z & !config != 0 {
+            Some(config | self.high_z)
+        } else {
+            None
+        };
+        let driven = self.pins & !self.high_z;
+        (
+            release,
+            (output & !driven) | self.high,
+            (config & !self.pins) | self.high_z,
+        )
+    }
+}
+
+/// Safe state of a device and the failure streak triggering it
+pub(crate) struct FailSafe {
+    state: Option<SafeState>,
+    /// Consecutive failed transfers applying the safe state
+    limit: Option<u16>,
+    /// Current streak of failed transfers
+    failures: u16,
+}
+
+impl FailSafe {
+    pub(crate) const fn new() -> Self {
+        Self {
+            state: None,
+            limit: None,
+            failures: 0,
+        }
+    }
+
+    /// Count the outcome of a transfer, returning the safe state if it has to be applied
+    const fn track(&mut self, failed: bool) -> Option<SafeState> {
+        if !failed {
+            self.failures = 0;
+            return None;
+        }
+
+        self.failures = self.failures.saturating_add(1);
+        match self.limit {
+            Some(limit) if self.failures == limit => self.state,
+            _ => None,
+        }
+    }
+}
+
+/// Bus write of `value` to all ports of `bank`, and its length
+fn bank_write<C: Chip>(bank: Bank, value: u32) -> ([u8; 1 + LARGEST_REG_SIZE_BYTES], usize) {
+    let mut buf = [0u8; 1 + LARGEST_REG_SIZE_BYTES];
+    buf[0] = bank_register::<C>(bank);
+    buf[1..].copy_from_slice(&value.to_le_bytes()[..LARGEST_REG_SIZE_BYTES]);
+    (buf, 1 + C::PORTS)
+}
+
+/// Bank value from register bytes
+fn bank_value(data: &[u8]) -> u32 {
+    data.iter().rev().fold(0, |acc, &byte| acc << 8 | u32::from(byte))
+}
+
+/// Bank writes applying `state` over the Output and Configuration register contents
+/// `current`, in order, and the Output value written
+///
+/// The Configuration is only written with `configure` set; `state` then keeps no
+/// high-impedance pin, so no pin is released either.
+fn safe_writes<C: Chip>(
+    state: SafeState,
+    current: [[u8; LARGEST_REG_SIZE_BYTES]; 2],
+    blind: bool,
+    configure: bool,
+) -> ([Option<(Bank, u32)>; 3], u32) {
+    let (release, output, config) = state.apply(
+        bank_value(&current[0][..C::PORTS]),
+        bank_value(&current[1][..C::PORTS]),
+        blind,
+    );
+    let writes = [
+        release.map(|release| (Bank::Configuration, release)),
+        Some((Bank::Output, output)),
+        configure.then_some((Bank::Configuration, config)),
+    ];
+    (writes, output)
+}
+
+/// Write `state` to the expander at `address` over a blocking bus, returning the Output
+/// value written
+fn write_blocking<C: Chip, I2c: embedded_hal::i2c::I2c>(
+    i2cbus: &mut I2c,
+    address: u8,
+    state: SafeState,
+    configure: bool,
+) -> Result<u32, I2c::Error> {
+    let mut current = [[0u8; LARGEST_REG_SIZE_BYTES]; 2];
+    let blind = all_pins::<C>() & !state.pins == 0;
+    if !blind {
+        for (bank, current) in [Bank::Output, Bank::Configuration].into_iter().zip(&mut current) {
+            i2cbus.write_read(address, &[bank_register::<C>(bank)], &mut current[..C::PORTS])?;
+        }
+    }
+
+    let (writes, output) = safe_writes::<C>(state, current, blind, configure);
+    for (bank, value) in writes.into_iter().flatten() {
+        let (buf, len) = bank_write::<C>(bank, value);
+        i2cbus.write(address, &buf[..len])?;
+    }
+    Ok(output)
+}
+
+/// Writes a safe state over a blocking I2C bus when dropped
+///
+/// Meant for a second handle to the bus the expander is on, so that the outputs fall back
+/// when the task owning the expander is torn down. The guard writes the safe state set, and
+/// the locks taken, when it was created by [`Pcal6416aDevice::safe_state_guard`].
+pub struct SafeStateGuard<I2c: embedded_hal::i2c::I2c, C: Chip = Pcal6416a> {
+    i2cbus: Option<I2c>,
+    address: u8,
+    state: SafeState,
+    configure: bool,
+    chip: PhantomData<C>,
+}
+
+impl<I2c: embedded_hal::i2c::I2c, C: Chip> SafeStateGuard<I2c, C> {
+    /// Get the state written on drop, without the pins left out by locks
+    #[must_use]
+    pub const fn state(&self) -> SafeState {
+        self.state
+    }
+
+    /// Take the bus back, so that dropping the guard writes nothing; `None` once taken
+    pub fn disarm(&mut self) -> Option<I2c> {
+        self.i2cbus.take()
+    }
+}
+
+impl<I2c: embedded_hal::i2c::I2c, C: Chip> Drop for SafeStateGuard<I2c, C> {
+    fn drop(&mut self) {
+        if let Some(i2cbus) = &mut self.i2cbus {
+            // Nothing left to report the error to
+            let _ = write_blocking::<C, _>(i2cbus, self.address, self.state, self.configure);
+        }
+    }
+}
+
+impl<I2c, C: Chip> Pcal6416aDevice<I2c, C> {
+    /// Set the safe state, see [`Pcal6416aDevice::set_safe_state`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if the state violates a registered [`Interlock`] or covers a locked pin.
+    #[must_use]
+    pub fn with_safe_state(mut self, state: SafeState) -> Self {
+        assert!(
+            self.set_safe_state(Some(state)).is_ok(),
+            "safe state violates an interlock or a lock"
+        );
+        self
+    }
+
+    /// Set the state the outputs fall back to, `None` for none
+    /// # Errors
+    ///
+    /// Will return `Err` with the state if it could leave two pins of a registered
+    /// [`Interlock`] high: a pin falling back high needs the other pins of its group
+    /// covered and falling back low. Also if it covers a locked pin or a pin of a locked
+    /// Output bank, or makes a pin high-impedance while the Configuration is locked or frozen
+    pub fn set_safe_state(&mut self, state: Option<SafeState>) -> Result<(), SafeState> {
+        if let Some(state) = state {
+            let (pins, configure) = self.protection.safe_scope();
+            if !self.interlocks.allow(state) || state.restrict(pins, configure) != state {
+                return Err(state);
+            }
+        }
+
+        self.fail_safe.state = state;
+        Ok(())
+    }
+
+    /// Get the state the outputs fall back to
+    #[must_use]
+    pub const fn safe_state(&self) -> Option<SafeState> {
+        self.fail_safe.state
+    }
+
+    /// Apply the safe state after `limit` consecutive failed bus transfers, see
+    /// [`Pcal6416aDevice::set_failure_limit`]
+    #[must_use]
+    pub const fn with_failure_limit(mut self, limit: u16) -> Self {
+        self.fail_safe.limit = Some(limit);
+        self
+    }
+
+    /// Apply the safe state once `limit` consecutive bus transfers failed, `None` to never
+    /// apply it on failures
+    ///
+    /// The streak ends with the next successful transfer.
+    pub const fn set_failure_limit(&mut self, limit: Option<u16>) {
+        self.fail_safe.limit = limit;
+    }
+
+    /// Get the number of consecutive failed bus transfers
+    #[must_use]
+    pub const fn consecutive_failures(&self) -> u16 {
+        self.fail_safe.failures
+    }
+
+    /// Guard writing the safe state over the blocking bus `i2cbus` when dropped, `None`
+    /// without a safe state
+    #[must_use]
+    pub fn safe_state_guard<G: embedded_hal::i2c::I2c>(&self, i2cbus: G) -> Option<SafeStateGuard<G, C>> {
+        let (pins, configure) = self.protection.safe_scope();
+        self.fail_safe.state.map(|state| SafeStateGuard {
+            i2cbus: Some(i2cbus),
+            address: self.i2c_address(),
+            state: state.restrict(pins, configure),
+            configure,
+            chip: PhantomData,
+        })
+    }
+
+    /// Record the Output register contents written by the safe state
+    fn observe_safe_outputs(&mut self, output: u32) {
+        self.interlocks.observe(all_pins::<C>(), output);
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, C: Chip> Pcal6416aDevice<I2c, C> {
+    /// Count the outcome of a bus transfer, applying the safe state at the failure limit
+    pub(crate) async fn track_async<T>(
+        &mut self,
+        result: Result<T, I2c::Error>,
+    ) -> Result<T, Pcal6416aError<I2c::Error>> {
+        if let Some(state) = self.fail_safe.track(result.is_err()) {
+            // Already failing, the transfer error is the one reported
+            let _ = self.write_safe_state_async(state).await;
+        }
+        result.map_err(Pcal6416aError::I2c)
+    }
+
+    /// Write `state` straight to the bus
+    pub(crate) async fn write_safe_state_async(&mut self, state: SafeState) -> Result<(), I2c::Error> {
+        let (pins, configure) = self.protection.safe_scope();
+        let state = state.restrict(pins, configure);
+        if state.pins == 0 {
+            return Ok(());
+        }
+
+        let address = self.i2c_address();
+        let mut current = [[0u8; LARGEST_REG_SIZE_BYTES]; 2];
+        let blind = all_pins::<C>() & !state.pins == 0;
+        if !blind {
+            for (bank, current) in [Bank::Output, Bank::Configuration].into_iter().zip(&mut current) {
+                self.i2cbus
+                    .write_read(address, &[bank_register::<C>(bank)], &mut current[..C::PORTS])
+                    .await?;
+            }
+        }
+
+        let (writes, output) = safe_writes::<C>(state, current, blind, configure);
+        for (bank, value) in writes.into_iter().flatten() {
+            let (buf, len) = bank_write::<C>(bank, value);
+            self.i2cbus.write(address, &buf[..len]).await?;
+        }
+        self.observe_safe_outputs(output);
+        Ok(())
+    }
+}
+
+impl<I2c: embedded_hal::i2c::I2c, C: Chip> Pcal6416aDevice<I2c, C> {
+    /// Count the outcome of a bus transfer, applying the safe state at the failure limit
+    pub(crate) fn track<T>(&mut self, result: Result<T, I2c::Error>) -> Result<T, Pcal6416aError<I2c::Error>> {
+        if let Some(state) = self.fail_safe.track(result.is_err()) {
+            // Already failing, the transfer error is the one reported
+            let _ = self.write_safe_state(state);
+        }
+        result.map_err(Pcal6416aError::I2c)
+    }
+
+    /// Write `state` straight to the bus
+    pub(crate) fn write_safe_state(&mut self, state: SafeState) -> Result<(), I2c::Error> {
+        let (pins, configure) = self.protection.safe_scope();
+        let state = state.restrict(pins, configure);
+        if state.pins == 0 {
+            return Ok(());
+        }
+
+        let address = self.i2c_address();
+        let output = write_blocking::<C, _>(&mut self.i2cbus, address, state, configure)?;
+        self.observe_safe_outputs(output);
+        Ok(())
+    }
+}
+
+impl<I2c: embedded_hal::i2c::I2c, C: Chip> Device<Pcal6416aDevice<I2c, C>> {
+    /// Apply the safe state, if one is set
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub fn apply_safe_state(&mut self) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let Some(state) = self.interface.fail_safe.state else {
+            return Ok(());
+        };
+        let result = self.interface.write_safe_state(state);
+        self.interface.track(result)
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, C: Chip> Device<Pcal6416aDevice<I2c, C>> {
+    /// Apply the safe state, if one is set (async version)
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn apply_safe_state_async(&mut self) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let Some(state) = self.interface.fail_safe.state else {
+            return Ok(());
+        };
+        let result = self.interface.write_safe_state_async(state).await;
+        self.interface.track_async(result).await
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> SharedDevice<I2c, M, C> {
+    /// Apply the safe state of the device, if one is set
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn apply_safe_state(&self) -> Result<(), Pcal6416aError<I2c::Error>> {
+        self.device.lock().await.apply_safe_state_async().await
+    }
+
+    /// Guard writing the safe state of the device over the blocking bus `i2cbus` when
+    /// dropped, see [`Pcal6416aDevice::safe_state_guard`]
+    pub async fn safe_state_guard<G: embedded_hal::i2c::I2c>(&self, i2cbus: G) -> Option<SafeStateGuard<G, C>> {
+        self.device.lock().await.interface.safe_state_guard(i2cbus)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embedded_hal::i2c::ErrorKind;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use crate::AddrPinState;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn failures_and_guard_apply_safe_state() {
+        // Every pin released first, as the HighZ pin 0_1 must not see its Output bit change
+        // while driven
+        let safe = [
+            Transaction::write(0x20, vec![0x06, 0xFF, 0xFF]),
+            Transaction::write(0x20, vec![0x02, 0x01, 0x00]),
+            Transaction::write(0x20, vec![0x06, 0x02, 0x00]),
+        ];
+        let mut expectations = vec![
+            Transaction::write_read(0x20, vec![0x00], vec![0x00]).with_error(ErrorKind::Other),
+            Transaction::write_read(0x20, vec![0x00], vec![0x00]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x00]).with_error(ErrorKind::Other),
+            Transaction::write_read(0x20, vec![0x00], vec![0x00]).with_error(ErrorKind::Other),
+        ];
+        // Applied by the second failure in a row, then by the guard on drop
+        expectations.extend_from_slice(&safe);
+        expectations.extend_from_slice(&safe);
+        let i2cbus = Mock::new(&expectations);
+        let state = SafeState::new()
+            .with_pins(0xFFFF, SafeLevel::Low)
+            .with_pin(Port::Port0, Pin::Pin0, SafeLevel::High)
+            .with_pin(Port::Port0, Pin::Pin1, SafeLevel::HighZ);
+        let dev: SharedDevice<_, NoopRawMutex> = SharedDevice::new(Device::new(
+            Pcal6416aDevice::new(AddrPinState::Low, i2cbus.clone())
+                .with_safe_state(state)
+                .with_failure_limit(2),
+        ));
+
+        let guard = dev.safe_state_guard(i2cbus.clone()).await.unwrap();
+        assert_eq!(guard.state(), state);
+
+        let [pin, ..] = dev.split();
+        for _ in 0..4 {
+            let _ = pin.is_high_async().await;
+        }
+        assert_eq!(dev.device.lock().await.interface.consecutive_failures(), 2);
+
+        drop(guard);
+        i2cbus.clone().done();
+    }
+
+    #[test]
+    fn partial_state_keeps_high_z_outputs() {
+        let i2cbus = Mock::new(&[
+            Transaction::write_read(0x20, vec![0x02], vec![0x06, 0x00]),
+            Transaction::write_read(0x20, vec![0x06], vec![0xF0, 0xFF]),
+            // Pin 0_1 released with its Output bit kept, then pin 0_2 driven low
+            Transaction::write(0x20, vec![0x06, 0xF2, 0xFF]),
+            Transaction::write(0x20, vec![0x02, 0x02, 0x00]),
+            Transaction::write(0x20, vec![0x06, 0xF2, 0xFF]),
+        ]);
+        let interlock = Interlock {
+            pins: 0x0003,
+            rule: crate::InterlockRule::AtMostOneHigh,
+        };
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus).with_interlock(interlock));
+
+        // Pin 0_1 may keep its Output bit high next to pin 0_0 driven high
+        let unsafe_state = SafeState::new()
+            .with_pin(Port::Port0, Pin::Pin0, SafeLevel::High)
+            .with_pin(Port::Port0, Pin::Pin1, SafeLevel::HighZ);
+        assert_eq!(dev.interface.set_safe_state(Some(unsafe_state)), Err(unsafe_state));
+
+        let state = SafeState::new()
+            .with_pin(Port::Port0, Pin::Pin1, SafeLevel::HighZ)
+            .with_pin(Port::Port0, Pin::Pin2, SafeLevel::Low);
+        dev.interface.set_safe_state(Some(state)).unwrap();
+        dev.interface.clear_interlocks();
+        assert!(dev.interface.add_interlock(interlock).is_ok());
+        dev.apply_safe_state().unwrap();
+
+        dev.interface.i2cbus.done();
+    }
+
+    #[test]
+    fn safe_state_respects_locks() {
+        let i2cbus = Mock::new(&[
+            Transaction::write_read(0x20, vec![0x02], vec![0x04, 0x00]),
+            Transaction::write_read(0x20, vec![0x06], vec![0x00, 0x00]),
+            // Pin 0_2 locked after the state was set keeps its level, Configuration left alone
+            Transaction::write(0x20, vec![0x02, 0x0C, 0x00]),
+        ]);
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus).with_locked_pins(0x0001));
+
+        let locked = SafeState::new().with_pin(Port::Port0, Pin::Pin0, SafeLevel::Low);
+        assert_eq!(dev.interface.set_safe_state(Some(locked)), Err(locked));
+
+        let state = SafeState::new()
+            .with_pin(Port::Port0, Pin::Pin2, SafeLevel::Low)
+            .with_pin(Port::Port0, Pin::Pin3, SafeLevel::High);
+        dev.interface.set_safe_state(Some(state)).unwrap();
+        dev.interface.lock_pins(0x0004);
+        dev.interface.lock_bank(Bank::Configuration);
+        let high_z = SafeState::new().with_pin(Port::Port0, Pin::Pin4, SafeLevel::HighZ);
+        assert_eq!(dev.interface.set_safe_state(Some(high_z)), Err(high_z));
+        dev.apply_safe_state().unwrap();
+
+        dev.interface.i2cbus.done();
+    }
+}
@@ -0,0 +1,329 @@
+//! Output interlocks.
+//!
+//! An [`Interlock`] registered on a [`Pcal6416aDevice`] guards a group of mutually exclusive
+//! outputs, e.g. the two sides of a half-bridge or the enables of two supplies feeding the
+//! same rail. Every write to the Output registers is checked against the registered
+//! interlocks, whichever API issued it, and a write that would leave two pins of a group
+//! high fails with [`Pcal6416aError::Interlock`] without reaching the bus.
+//!
+//! With [`InterlockRule::BreakBeforeMake`], a write switching over from one pin of a group
+//! to another is split: the pin going low is written first, and the pin going high only
+//! once the dead time has elapsed. If it has not, the write fails after its first part,
+//! which only drives the outgoing pin low and leaves every other output as it was; retrying
+//! the write after the dead time completes the switch-over.
+//!
+//! Interlocks only look at the Output registers, the guarded pins are expected to stay
+//! outputs.
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+
+use crate::{Bank, Chip, Pcal6416aDevice, Port, SafeState, SharedDevice};
+
+/// Number of interlocks a device holds
+pub const MAX_INTERLOCKS: usize = 4;
+
+/// Rule enforced on the pins of an [`Interlock`]
+#[derive(Debug, Clone, Copy)]
+pub enum InterlockRule {
+    /// At most one pin of the group is high
+    AtMostOneHigh,
+    /// At most one pin of the group is high, and a pin only goes high `dead_time_ms` after
+    /// the last pin of the group went low
+    BreakBeforeMake {
+        /// Time all pins of the group stay low on a switch-over, in milliseconds
+        dead_time_ms: u32,
+        /// Monotonic clock in milliseconds, e.g. `|| embassy_time::Instant::now().as_millis()`
+        now_ms: fn() -> u64,
+    },
+}
+
+/// Group of mutually exclusive outputs
+#[derive(Debug, Clone, Copy)]
+pub struct Interlock {
+    /// Pins of the group, bit `port * 8 + pin`
+    pub pins: u32,
+    /// Rule enforced on the group
+    pub rule: InterlockRule,
+}
+
+/// Registered interlock with its switch-over state
+#[derive(Clone, Copy)]
+struct Group {
+    interlock: Interlock,
+    /// Time a pin of the group last went low
+    broken_at: Option<u64>,
+}
+
+/// Interlocks of a device and the Output register state they are checked against
+pub(crate) struct Interlocks {
+    groups: [Option<Group>; MAX_INTERLOCKS],
+    /// Last known Output register contents
+    outputs: u32,
+    /// Bits of `outputs` known
+    known: u32,
+}
+
+impl Interlocks {
+    pub(crate) const fn new() -> Self {
+        Self {
+            groups: [None; MAX_INTERLOCKS],
+            outputs: 0,
+            known: 0,
+        }
+    }
+
+    /// Output bits to read before a write to `mask` can be checked
+    ///
+    /// All pins of the groups involved are needed, those written included, to tell which
+    /// pins rise and fall. A break-before-make group also needs every written bit, as the
+    /// break rewrites the bits it leaves as they are.
+    pub(crate) fn missing(&self, mask: u32) -> u32 {
+        let mut needed = 0;
+        for group in self.groups.iter().flatten() {
+            let pins = group.interlock.pins;
+            if pins & mask == 0 {
+                continue;
+            }
+
+            needed |= pins;
+            if matches!(group.interlock.rule, InterlockRule::BreakBeforeMake { .. }) {
+                needed |= mask;
+            }
+        }
+        needed & !self.known
+    }
+
+    /// Whether `state` satisfies all registered interlocks
+    pub(crate) fn allow(&self, state: SafeState) -> bool {
+        self.groups
+            .iter()
+            .flatten()
+            .all(|group| state.satisfies(&group.interlock))
+    }
+
+    /// Record Output register contents read or written
+    pub(crate) const fn observe(&mut self, mask: u32, values: u32) {
+        self.outputs = (self.outputs & !mask) | (values & mask);
+        self.known |= mask;
+    }
+
+    /// Check a write of `values` to the output bits of `mask`
+    ///
+    /// Returns the values to write first to break a switch-over, if any, and whether the
+    /// write may then complete. The break only clears the pins leaving their group high;
+    /// every other output keeps its current value.
+    pub(crate) fn check(&self, mask: u32, values: u32) -> (Option<u32>, bool) {
+        let current = self.outputs;
+        let next = (current & !mask) | (values & mask);
+        let mut first = None;
+        let mut allowed = true;
+        for group in self.groups.iter().flatten() {
+            let pins = group.interlock.pins;
+            if (next & pins).count_ones() > 1 {
+                return (None, false);
+            }
+
+            if let InterlockRule::BreakBeforeMake { dead_time_ms, now_ms } = group.interlock.rule {
+                let rising = next & pins & !current;
+                if rising == 0 {
+                    continue;
+                }
+
+                let now = now_ms();
+                let falling = current & pins & !next;
+                let broken_at = if falling != 0 {
+                    first = Some(first.unwrap_or(current) & !falling);
+                    Some(now)
+                } else {
+                    group.broken_at
+                };
+                if broken_at.is_some_and(|at| now.wrapping_sub(at) < u64::from(dead_time_ms)) {
+                    allowed = false;
+                }
+            }
+        }
+
+        (first, allowed)
+    }
+
+    /// Record a completed write of `values` to the output bits of `mask`
+    pub(crate) fn commit(&mut self, mask: u32, values: u32) {
+        let next = (self.outputs & !mask) | (values & mask);
+        for group in self.groups.iter_mut().flatten() {
+            if let InterlockRule::BreakBeforeMake { now_ms, .. } = group.interlock.rule
+                && self.outputs & group.interlock.pins & !next != 0
+            {
+                group.broken_at = Some(now_ms());
+            }
+        }
+        self.observe(mask, values);
+    }
+}
+
+/// Port whose Output register is at `register`
+fn output_port<C: Chip>(register: u8) -> Option<Port> {
+    [Port::Port0, Port::Port1, Port::Port2]
+        .into_iter()
+        .take(C::PORTS)
+        .find(|&port| C::register(Bank::Output, port) == register)
+}
+
+/// Output bits accessed by a transfer of `data` at `address`, and their values
+pub(crate) fn output_bits<C: Chip>(address: u8, data: &[u8]) -> (u32, u32) {
+    let mut mask = 0;
+    let mut values = 0;
+    for (register, &byte) in (address & !C::AUTO_INCREMENT..).zip(data) {
+        if let Some(port) = output_port::<C>(register) {
+            let shift = 8 * u32::from(port.index());
+            mask |= 0xFF << shift;
+            values |= u32::from(byte) << shift;
+        }
+    }
+    (mask, values)
+}
+
+/// Replace the output bytes of a write of `data` at `address` with those of `values`
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn set_output_bits<C: Chip>(address: u8, data: &mut [u8], values: u32) {
+    for (register, byte) in (address & !C::AUTO_INCREMENT..).zip(data) {
+        if let Some(port) = output_port::<C>(register) {
+            *byte = (values >> (8 * port.index())) as u8;
+        }
+    }
+}
+
+impl<I2c, C: Chip> Pcal6416aDevice<I2c, C> {
+    /// Register an interlock, see [`Pcal6416aDevice::add_interlock`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`MAX_INTERLOCKS`] interlocks are already registered or the safe state
+    /// violates the interlock.
+    #[must_use]
+    pub fn with_interlock(mut self, interlock: Interlock) -> Self {
+        assert!(self.add_interlock(interlock).is_ok(), "interlock not registered");
+        self
+    }
+
+    /// Register an interlock, enforced on every later write to the Output registers
+    ///
+    /// The current state of the pins is not checked; it is read back on the next write
+    /// involving them.
+    /// # Errors
+    ///
+    /// Will return `Err` with the interlock if [`MAX_INTERLOCKS`] are already registered,
+    /// or if the safe state violates it, see [`Pcal6416aDevice::set_safe_state`]
+    pub fn add_interlock(&mut self, interlock: Interlock) -> Result<(), Interlock> {
+        if let Some(state) = self.safe_state()
+            && !state.satisfies(&interlock)
+        {
+            return Err(interlock);
+        }
+
+        let Some(slot) = self.interlocks.groups.iter_mut().find(|group| group.is_none()) else {
+            return Err(interlock);
+        };
+        *slot = Some(Group {
+            interlock,
+            broken_at: None,
+        });
+        Ok(())
+    }
+
+    /// Remove all interlocks
+    pub fn clear_interlocks(&mut self) {
+        self.interlocks.groups = [None; MAX_INTERLOCKS];
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> SharedDevice<I2c, M, C> {
+    /// Register an interlock on the device, see [`Pcal6416aDevice::add_interlock`]
+    /// # Errors
+    ///
+    /// Will return `Err` with the interlock if [`MAX_INTERLOCKS`] are already registered,
+    /// or if the safe state violates it
+    pub async fn add_interlock(&self, interlock: Interlock) -> Result<(), Interlock> {
+        self.device.lock().await.interface.add_interlock(interlock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use crate::{AddrPinState, Device, Pcal6416aDevice, Pcal6416aError, Pin};
+
+    use super::*;
+
+    static NOW: AtomicU64 = AtomicU64::new(0);
+
+    fn now_ms() -> u64 {
+        NOW.load(Ordering::Relaxed)
+    }
+
+    #[test]
+    fn writes_are_checked() {
+        let i2cbus = Mock::new(&[
+            // Pin 0_0 high, port 1 read back for the group spanning both ports
+            Transaction::write_read(0x20, vec![0x02], vec![0x00]),
+            Transaction::write_read(0x20, vec![0x02], vec![0x00, 0x00]),
+            Transaction::write(0x20, vec![0x02, 0x01]),
+            // Pin 1_0 high is rejected
+            Transaction::write_read(0x20, vec![0x03], vec![0x00]),
+            // Pin 0_1 high, then switch-over to 0_2 broken and rejected within the dead time,
+            // the break leaving pin 0_7 for the retry
+            Transaction::write_read(0x20, vec![0x02], vec![0x01]),
+            Transaction::write(0x20, vec![0x02, 0x03]),
+            Transaction::write(0x20, vec![0x02, 0x01, 0x00]),
+            // Completed after the dead time
+            Transaction::write(0x20, vec![0x02, 0x85, 0x00]),
+        ]);
+        let mut dev = Device::new(
+            Pcal6416aDevice::new(AddrPinState::Low, i2cbus)
+                .with_interlock(Interlock {
+                    pins: 0x0101,
+                    rule: InterlockRule::AtMostOneHigh,
+                })
+                .with_interlock(Interlock {
+                    pins: 0x0006,
+                    rule: InterlockRule::BreakBeforeMake {
+                        dead_time_ms: 5,
+                        now_ms,
+                    },
+                }),
+        );
+
+        dev.set_pin_high(Port::Port0, Pin::Pin0).unwrap();
+        assert_eq!(dev.set_pin_high(Port::Port1, Pin::Pin0), Err(Pcal6416aError::Interlock));
+        dev.set_pin_high(Port::Port0, Pin::Pin1).unwrap();
+        assert_eq!(dev.write_bank(Bank::Output, 0x0085), Err(Pcal6416aError::Interlock));
+        NOW.store(5, Ordering::Relaxed);
+        dev.write_bank(Bank::Output, 0x0085).unwrap();
+
+        dev.interface.i2cbus.done();
+    }
+
+    #[test]
+    fn blind_writes_are_read_back() {
+        let i2cbus = Mock::new(&[
+            // Outputs unknown: read back, finding pin 0_1 high, and broken
+            Transaction::write_read(0x20, vec![0x02], vec![0x02, 0x10]),
+            Transaction::write(0x20, vec![0x02, 0x00, 0x10]),
+        ]);
+        let mut dev = Device::new(
+            Pcal6416aDevice::new(AddrPinState::Low, i2cbus).with_interlock(Interlock {
+                pins: 0x0006,
+                rule: InterlockRule::BreakBeforeMake {
+                    dead_time_ms: 5,
+                    now_ms,
+                },
+            }),
+        );
+
+        assert_eq!(dev.write_bank(Bank::Output, 0x0004), Err(Pcal6416aError::Interlock));
+
+        dev.interface.i2cbus.done();
+    }
+}
@@ -0,0 +1,787 @@
+//! Interrupt handling and edge detection.
+//!
+//! The expander has no edge selection: any change on an unmasked input asserts the
+//! open-drain INT output until the input registers are read. Edges are recovered by
+//! comparing each input sample against the last snapshot held by the [`SharedDevice`],
+//! and routed to the [`IoPin`]s waiting on them. Every input read made through a
+//! [`SharedDevice`] goes through the snapshot, so reading a pin never swallows an edge
+//! of another pin on the same port.
+//!
+//! Several expanders may share one INT line (wired-OR). [`InterruptDispatcher`] services
+//! all of them until the line is released.
+
+use core::future::poll_fn;
+use core::task::Poll;
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::waitqueue::WakerRegistration;
+
+use crate::demand::{DemandTracker, Waiter};
+use crate::storm::{StormTracker, mask_stormed};
+use crate::{Bank, Chip, Device, IoPin, Pcal6416aDevice, Pcal6416aError, Port, SharedDevice};
+
+/// Maximum number of concurrent [`SharedDevice::wait_for_any`] calls per device, keypad
+/// scans waiting for a press included
+///
+/// Each call holds a wake slot of its own while waiting; a call finding all slots taken
+/// fails with [`Pcal6416aError::TooManyWaiters`].
+pub const ANY_WAITERS: usize = 4;
+
+/// Largest number of pins on a single expander
+pub(crate) const MAX_PINS: usize = 24;
+
+/// Direction of an input transition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Edge {
+    /// Low to high
+    Rising,
+    /// High to low
+    Falling,
+}
+
+/// Edges reported for a pin
+///
+/// The expander flags any change of an input; unwanted edges are dropped in software by
+/// comparing each sample with the previous one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EdgeFilter {
+    /// Rising edges only
+    Rising,
+    /// Falling edges only
+    Falling,
+    /// Both edges
+    #[default]
+    Both,
+}
+
+impl EdgeFilter {
+    /// Whether rising and falling edges pass the filter
+    const fn edges(self) -> (bool, bool) {
+        match self {
+            Self::Rising => (true, false),
+            Self::Falling => (false, true),
+            Self::Both => (true, true),
+        }
+    }
+}
+
+/// Input snapshot and edge bookkeeping of one expander.
+///
+/// Pin masks use bit `port * 8 + pin`.
+pub(crate) struct EventState {
+    /// Last sampled input levels
+    levels: u32,
+    /// Pins with a valid level in `levels`
+    known: u32,
+    /// Rising edge sequence number per pin
+    rising: [u32; MAX_PINS],
+    /// Falling edge sequence number per pin
+    falling: [u32; MAX_PINS],
+    /// Task waiting on each pin
+    wakers: [WakerRegistration; MAX_PINS],
+    /// Tasks waiting on a set of pins, one slot each
+    any_wakers: [WakerRegistration; ANY_WAITERS],
+    /// Slots of `any_wakers` taken, one bit each
+    any_slots: u8,
+    /// Pins reporting rising edges
+    rising_enabled: u32,
+    /// Pins reporting falling edges
+    falling_enabled: u32,
+    /// Edge rate tracking for storm protection
+    pub(crate) storm: StormTracker,
+    /// Waiter bookkeeping for demand masking
+    pub(crate) demand: DemandTracker,
+    /// Edge sequence numbers at the last counter reset per pin
+    pub(crate) count_base: [(u32, u32); MAX_PINS],
+    /// Samples merged so far, wrapping
+    samples: u32,
+}
+
+/// Outcome of merging an input sample, as pin masks
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Changes {
+    /// Input levels after the sample
+    pub(crate) levels: u32,
+    /// Pins that rose, after edge filtering
+    pub(crate) rising: u32,
+    /// Pins that fell, after edge filtering
+    pub(crate) falling: u32,
+    /// Pins flagged by Interrupt Status with an unchanged level, after edge filtering
+    pub(crate) pulsed: u32,
+    /// Pins newly masked by storm protection
+    pub(crate) stormed: u32,
+    /// Sequence number of the sample
+    pub(crate) sample: u32,
+}
+
+impl Changes {
+    /// Pins with anything to report
+    pub(crate) const fn pins(&self) -> u32 {
+        self.rising | self.falling | self.pulsed | self.stormed
+    }
+}
+
+impl EventState {
+    pub(crate) const fn new() -> Self {
+        Self {
+            levels: 0,
+            known: 0,
+            rising: [0; MAX_PINS],
+            falling: [0; MAX_PINS],
+            wakers: [const { WakerRegistration::new() }; MAX_PINS],
+            any_wakers: [const { WakerRegistration::new() }; ANY_WAITERS],
+            any_slots: 0,
+            rising_enabled: u32::MAX,
+            falling_enabled: u32::MAX,
+            storm: StormTracker::new(),
+            demand: DemandTracker::new(),
+            count_base: [(0, 0); MAX_PINS],
+            samples: 0,
+        }
+    }
+
+    /// Merge a sample of the pins in `valid`, `flagged` holding the pins reported by
+    /// the Interrupt Status registers
+    ///
+    /// A flagged pin whose level matches the snapshot toggled twice since the last sample:
+    /// it is reported as a pulse, counting as both edges. Pins sampled for the first time
+    /// only seed the snapshot. Filtered edges still update the snapshot and wake level
+    /// waiters; edges of pins over the storm limit only update the snapshot.
+    fn update(&mut self, valid: u32, levels: u32, flagged: u32) -> Changes {
+        let compared = valid & self.known & !self.storm.tripped();
+        let changed = (levels ^ self.levels) & compared;
+        let pulsed = flagged & compared & !changed;
+        self.levels = (self.levels & !valid) | (levels & valid);
+        self.known |= valid;
+        self.samples = self.samples.wrapping_add(1);
+
+        let stormed = self.storm.track(changed | pulsed);
+        let (changed, pulsed) = (changed & !stormed, pulsed & !stormed);
+
+        let (rising, falling) = (changed & levels, changed & !levels);
+        for index in bits(changed | pulsed) {
+            let bit = 1 << index;
+            if (rising | pulsed) & bit != 0 {
+                self.rising[index] = self.rising[index].wrapping_add(1);
+            }
+            if (falling | pulsed) & bit != 0 {
+                self.falling[index] = self.falling[index].wrapping_add(1);
+            }
+            self.wakers[index].wake();
+        }
+        if changed | pulsed != 0 {
+            for waker in &mut self.any_wakers {
+                waker.wake();
+            }
+        }
+
+        Changes {
+            levels: self.levels,
+            rising: rising & self.rising_enabled,
+            falling: falling & self.falling_enabled,
+            pulsed: pulsed & (self.rising_enabled | self.falling_enabled),
+            stormed,
+            sample: self.samples,
+        }
+    }
+
+    fn set_filter(&mut self, pins: u32, filter: EdgeFilter) {
+        let (rising, falling) = filter.edges();
+        self.rising_enabled = if rising {
+            self.rising_enabled | pins
+        } else {
+            self.rising_enabled & !pins
+        };
+        self.falling_enabled = if falling {
+            self.falling_enabled | pins
+        } else {
+            self.falling_enabled & !pins
+        };
+    }
+
+    /// Whether rising and falling edges of pin `index` pass its filter
+    const fn filter(&self, index: usize) -> (bool, bool) {
+        (
+            self.rising_enabled & (1 << index) != 0,
+            self.falling_enabled & (1 << index) != 0,
+        )
+    }
+
+    /// Edge sequence numbers of pin `index`
+    pub(crate) const fn sequence(&self, index: usize) -> (u32, u32) {
+        (self.rising[index], self.falling[index])
+    }
+}
+
+/// Indices of the set bits of `mask`
+pub(crate) fn bits(mask: u32) -> impl Iterator<Item = usize> {
+    (0..MAX_PINS).filter(move |index| mask & (1 << index) != 0)
+}
+
+/// Mask of the pins of `port`
+const fn port_mask(port: Port) -> u32 {
+    0xFF << (port.index() * 8)
+}
+
+/// Wake slot of a task waiting on a set of pins, freed on drop
+pub(crate) struct AnySlot<'a, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> {
+    shared: &'a SharedDevice<I2c, M, C>,
+    index: usize,
+}
+
+impl<'a, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> AnySlot<'a, I2c, M, C> {
+    /// Take a free slot, if any
+    pub(crate) fn take(shared: &'a SharedDevice<I2c, M, C>) -> Option<Self> {
+        shared.with_events(|events| {
+            let index = (0..ANY_WAITERS).find(|index| events.any_slots & (1 << index) == 0)?;
+            events.any_slots |= 1 << index;
+            Some(Self { shared, index })
+        })
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> Drop for AnySlot<'_, I2c, M, C> {
+    fn drop(&mut self) {
+        self.shared.with_events(|events| {
+            events.any_slots &= !(1 << self.index);
+            events.any_wakers[self.index] = WakerRegistration::new();
+        });
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> SharedDevice<I2c, M, C> {
+    pub(crate) fn with_events<R>(&self, f: impl FnOnce(&mut EventState) -> R) -> R {
+        self.events.lock(|events| f(&mut events.borrow_mut()))
+    }
+
+    /// Service a pending interrupt of this expander
+    ///
+    /// Reads the Interrupt Status registers and, if any pin is flagged, the input
+    /// registers, which acknowledges the interrupt. Edges found against the last input
+    /// snapshot are routed to the waiting pins. A flagged pin whose level did not change
+    /// pulsed in between and is reported as [`crate::EventKind::Pulse`]; enabling the input
+    /// latch ([`Bank::InputLatch`]) on such pins holds the first transition until read, so
+    /// pulses shorter than the service latency show up as edges. Parts without Interrupt
+    /// Status registers (see [`crate::Variant`]) always read the inputs.
+    ///
+    /// Returns the pins flagged or found changed, bit `port * 8 + pin`.
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn service_interrupt(&self) -> Result<u32, Pcal6416aError<I2c::Error>> {
+        let mut device = self.device.lock().await;
+
+        let status = if device.interface.variant.has_agile_io() {
+            let status = device.read_bank_async(Bank::InterruptStatus).await?;
+            if status == 0 {
+                return Ok(0);
+            }
+            status
+        } else {
+            0
+        };
+
+        let levels = device.read_bank_async(Bank::Input).await?;
+        let changes = self.record(&mut device, all_pins::<C>(), levels, status).await?;
+        Ok(status | changes.pins())
+    }
+
+    /// Select the edges reported for the pins in `pins`, bit `port * 8 + pin`
+    ///
+    /// Applies to published events and [`embedded_hal_async::digital::Wait::wait_for_any_edge`].
+    /// All pins report both edges by default.
+    pub fn set_edge_filter(&self, pins: u32, filter: EdgeFilter) {
+        self.with_events(|events| events.set_filter(pins, filter));
+    }
+
+    /// Read all input registers in one transaction, merging them into the snapshot
+    pub(crate) async fn sample_inputs(&self) -> Result<Changes, Pcal6416aError<I2c::Error>> {
+        let mut device = self.device.lock().await;
+        self.sync_demand(&mut device).await?;
+        let levels = device.read_bank_async(Bank::Input).await?;
+        self.record(&mut device, all_pins::<C>(), levels, 0).await
+    }
+
+    /// Read the input register of `port`, merging it into the snapshot
+    pub(crate) async fn sample_port(&self, port: Port) -> Result<u8, Pcal6416aError<I2c::Error>> {
+        let mut device = self.device.lock().await;
+        // Unmask new waiters before sampling, so no edge falls in between
+        self.sync_demand(&mut device).await?;
+        let value = device.read_port_async(Bank::Input, port).await?;
+        self.record(&mut device, port_mask(port), u32::from(value) << (port.index() * 8), 0)
+            .await?;
+        Ok(value)
+    }
+
+    /// Merge an input sample of the pins in `valid`, waking waiters, publishing events
+    /// and updating the Interrupt Mask registers
+    pub(crate) async fn record(
+        &self,
+        device: &mut Device<Pcal6416aDevice<I2c, C>>,
+        valid: u32,
+        levels: u32,
+        flagged: u32,
+    ) -> Result<Changes, Pcal6416aError<I2c::Error>> {
+        let changes = self.with_events(|events| events.update(valid, levels, flagged));
+        self.publish(&changes);
+        if self.demand_masking() {
+            self.sync_demand(device).await?;
+        } else {
+            mask_stormed(device, changes.stormed).await?;
+        }
+        Ok(changes)
+    }
+
+    /// Wait for an edge on any of the pins in `pins`, bit `port * 8 + pin`
+    ///
+    /// Edges are found by whatever services the device, normally an
+    /// [`InterruptDispatcher`] reading the Interrupt Status registers, so a single future
+    /// covers all the pins. `edge` selects the edges waited for; the pins' own
+    /// [`EdgeFilter`]s do not apply. With demand masking the pins are unmasked while waiting.
+    ///
+    /// Returns the pins that fired, bit `port * 8 + pin`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let fired = shared.wait_for_any(POWER_BUTTON | LID | DOCK, EdgeFilter::Both).await?;
+    /// if fired & LID != 0 { /* lid moved */ }
+    /// ```
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails, or
+    /// [`Pcal6416aError::TooManyWaiters`] if [`ANY_WAITERS`] calls are already waiting
+    pub async fn wait_for_any(&self, pins: u32, edge: EdgeFilter) -> Result<u32, Pcal6416aError<I2c::Error>> {
+        let pins = pins & all_pins::<C>();
+        let slot = AnySlot::take(self).ok_or(Pcal6416aError::TooManyWaiters)?;
+        let waiter = Waiter::new(self, pins);
+        self.sample_inputs().await?;
+
+        let since = self.sequences();
+        let fired = self.wait_for_edges_since(&slot, pins, &since, edge.edges()).await;
+        waiter.release().await?;
+        Ok(fired)
+    }
+
+    /// Snapshot of the rising and falling edge sequence numbers of all pins
+    pub(crate) fn sequences(&self) -> ([u32; MAX_PINS], [u32; MAX_PINS]) {
+        self.with_events(|events| (events.rising, events.falling))
+    }
+
+    /// Wait until one of `pins` sees one of `edges` (rising, falling) after `since`,
+    /// returning the pins that did
+    pub(crate) async fn wait_for_edges_since(
+        &self,
+        slot: &AnySlot<'_, I2c, M, C>,
+        pins: u32,
+        since: &([u32; MAX_PINS], [u32; MAX_PINS]),
+        (rising, falling): (bool, bool),
+    ) -> u32 {
+        poll_fn(|cx| {
+            self.with_events(|events| {
+                let fired = bits(pins)
+                    .filter(|&index| {
+                        (rising && events.rising[index] != since.0[index])
+                            || (falling && events.falling[index] != since.1[index])
+                    })
+                    .fold(0, |fired, index| fired | 1 << index);
+                if fired == 0 {
+                    events.any_wakers[slot.index].register(cx.waker());
+                    Poll::Pending
+                } else {
+                    Poll::Ready(fired)
+                }
+            })
+        })
+        .await
+    }
+
+    /// Wait until pin `index` sees one of `edges` after `since`
+    async fn wait_for_sequence(&self, index: usize, since: (u32, u32), edges: (bool, bool)) {
+        poll_fn(|cx| {
+            self.with_events(|events| {
+                let (rising, falling) = events.sequence(index);
+                if (edges.0 && rising != since.0) || (edges.1 && falling != since.1) {
+                    Poll::Ready(())
+                } else {
+                    events.wakers[index].register(cx.waker());
+                    Poll::Pending
+                }
+            })
+        })
+        .await;
+    }
+}
+
+/// Mask of all pins of chip `C`
+#[allow(clippy::cast_possible_truncation)] // at most 24 pins
+pub(crate) const fn all_pins<C: Chip>() -> u32 {
+    u32::MAX >> (32 - C::PINS as u32)
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> IoPin<'_, I2c, M, C> {
+    /// Select the edges reported for this pin, see [`SharedDevice::set_edge_filter`]
+    pub fn set_edge_filter(&self, filter: EdgeFilter) {
+        self.shared.set_edge_filter(1 << self.index(), filter);
+    }
+
+    /// Sample this pin and wait for one of `edges` (rising, falling) afterwards
+    ///
+    /// Returns immediately if `until` is given and the sampled level already matches it.
+    pub(crate) async fn wait_for(
+        &self,
+        edges: (bool, bool),
+        until: Option<bool>,
+    ) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let index = usize::from(self.index());
+        let waiter = Waiter::new(self.shared, 1 << index);
+        let high = self.shared.sample_port(self.port).await? & self.pin.mask() != 0;
+        if until != Some(high) {
+            let since = self.shared.with_events(|events| events.sequence(index));
+            self.shared.wait_for_sequence(index, since, edges).await;
+        }
+
+        waiter.release().await
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> embedded_hal_async::digital::Wait
+    for IoPin<'_, I2c, M, C>
+{
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        self.wait_for((true, false), Some(true)).await
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        self.wait_for((false, true), Some(false)).await
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        self.wait_for((true, false), None).await
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        self.wait_for((false, true), None).await
+    }
+
+    /// Wait for an edge passing the pin's [`EdgeFilter`]
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        let edges = self
+            .shared
+            .with_events(|events| events.filter(usize::from(self.index())));
+        self.wait_for(edges, None).await
+    }
+}
+
+/// Error raised while dispatching a shared interrupt line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InterruptError<P, E> {
+    /// Error reading the MCU pin connected to INT
+    Int(P),
+    /// Error servicing an expander
+    Device(Pcal6416aError<E>),
+}
+
+impl<P, E> From<Pcal6416aError<E>> for InterruptError<P, E> {
+    fn from(error: Pcal6416aError<E>) -> Self {
+        Self::Device(error)
+    }
+}
+
+/// Dispatcher for an INT line shared by `N` expanders (wired-OR).
+///
+/// The INT outputs are open-drain, so the line stays low while any expander has a pending
+/// interrupt. On each assertion every device is serviced in turn with
+/// [`SharedDevice::service_interrupt`], repeating until the line is released, so an edge
+/// arriving while another device is being serviced is not lost.
+///
+/// # Example
+/// ```ignore
+/// let left = SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2c_left)));
+/// let right = SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::High, i2c_right)));
+/// let mut dispatcher = InterruptDispatcher::new(int_pin, [&left, &right]);
+///
+/// // In a dedicated task
+/// dispatcher.run().await?;
+/// ```
+pub struct InterruptDispatcher<'a, Int, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip, const N: usize> {
+    int: Int,
+    devices: [&'a SharedDevice<I2c, M, C>; N],
+}
+
+impl<'a, Int, I2c, M, C, const N: usize> InterruptDispatcher<'a, Int, I2c, M, C, N>
+where
+    Int: embedded_hal::digital::InputPin + embedded_hal_async::digital::Wait,
+    I2c: embedded_hal_async::i2c::I2c,
+    M: RawMutex,
+    C: Chip,
+{
+    /// Create a dispatcher for the expanders in `devices`, whose INT outputs are tied to `int`
+    pub const fn new(int: Int, devices: [&'a SharedDevice<I2c, M, C>; N]) -> Self {
+        Self { int, devices }
+    }
+
+    /// Service all devices until the INT line is released
+    /// # Errors
+    ///
+    /// Will return `Err` if reading the INT pin or an underlying I2C bus operation fails
+    pub async fn service(&mut self) -> Result<(), InterruptError<Int::Error, I2c::Error>> {
+        loop {
+            for device in self.devices {
+                device.service_interrupt().await?;
+            }
+
+            if self.int.is_high().map_err(InterruptError::Int)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Wait for the INT line to assert and service it, forever
+    /// # Errors
+    ///
+    /// Will return `Err` if reading the INT pin or an underlying I2C bus operation fails
+    pub async fn run(&mut self) -> Result<core::convert::Infallible, InterruptError<Int::Error, I2c::Error>> {
+        loop {
+            self.int.wait_for_low().await.map_err(InterruptError::Int)?;
+            self.service().await?;
+        }
+    }
+
+    /// Release the INT pin
+    pub fn into_inner(self) -> Int {
+        self.int
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embedded_hal_async::digital::Wait;
+    use embedded_hal_mock::eh1::MockError;
+    use embedded_hal_mock::eh1::digital::{Mock as PinMock, State, Transaction as PinTransaction};
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use super::*;
+    use crate::{AddrPinState, Device, EventKind, Pcal6416aDevice};
+
+    #[tokio::test]
+    async fn shared_line_dispatch() {
+        let low = Mock::new(&[
+            Transaction::write_read(0x20, vec![0x01], vec![0x00]),
+            // Pin 1_3 flagged and now high
+            Transaction::write_read(0x20, vec![0x4C], vec![0x00, 0x08]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x00, 0x08]),
+            Transaction::write_read(0x20, vec![0x4C], vec![0x00, 0x00]),
+        ]);
+        let high = Mock::new(&[
+            Transaction::write_read(0x21, vec![0x4C], vec![0x00, 0x00]),
+            // Interrupt raised while the first pass was running
+            Transaction::write_read(0x21, vec![0x4C], vec![0x01, 0x00]),
+            Transaction::write_read(0x21, vec![0x00], vec![0x01, 0x00]),
+        ]);
+        let int = PinMock::new(&[
+            PinTransaction::wait_for_state(State::Low),
+            PinTransaction::get(State::Low),
+            PinTransaction::get(State::High),
+            PinTransaction::wait_for_state(State::Low).with_error(MockError::Io(std::io::ErrorKind::Other)),
+        ]);
+
+        let left: SharedDevice<_, NoopRawMutex> =
+            SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, low)));
+        let right: SharedDevice<_, NoopRawMutex> =
+            SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::High, high)));
+        let mut dispatcher = InterruptDispatcher::new(int, [&left, &right]);
+
+        {
+            let mut pins = left.split();
+            let mut wait = core::pin::pin!(pins[11].wait_for_rising_edge());
+            assert!(poll_once(wait.as_mut()).await.is_pending());
+
+            assert!(matches!(dispatcher.run().await, Err(InterruptError::Int(_))));
+            assert!(poll_once(wait.as_mut()).await.is_ready());
+        }
+
+        dispatcher.into_inner().done();
+        left.device.lock().await.interface.i2cbus.done();
+        right.device.lock().await.interface.i2cbus.done();
+    }
+
+    #[tokio::test]
+    async fn edge_direction() {
+        let i2cbus = Mock::new(&[
+            Transaction::write_read(0x20, vec![0x00], vec![0x00]),
+            Transaction::write_read(0x20, vec![0x4C], vec![0x01, 0x00]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x01, 0x00]),
+            Transaction::write_read(0x20, vec![0x4C], vec![0x01, 0x00]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x00, 0x00]),
+        ]);
+        let dev: SharedDevice<_, NoopRawMutex> =
+            SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus)));
+
+        {
+            let mut pins = dev.split();
+            let mut wait = core::pin::pin!(pins[0].wait_for_falling_edge());
+            assert!(poll_once(wait.as_mut()).await.is_pending());
+
+            // Release does not wake a falling edge waiter
+            assert_eq!(dev.service_interrupt().await.unwrap(), 0x0001);
+            assert!(poll_once(wait.as_mut()).await.is_pending());
+
+            assert_eq!(dev.service_interrupt().await.unwrap(), 0x0001);
+            assert!(poll_once(wait.as_mut()).await.is_ready());
+        }
+
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+
+    #[tokio::test]
+    async fn no_status_no_input_read() {
+        let i2cbus = Mock::new(&[Transaction::write_read(0x20, vec![0x4C], vec![0x00, 0x00])]);
+        let dev: SharedDevice<_, NoopRawMutex> =
+            SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus)));
+
+        assert_eq!(dev.service_interrupt().await.unwrap(), 0);
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+
+    #[tokio::test]
+    async fn filtered_edges() {
+        let i2cbus = Mock::new(&[
+            Transaction::write_read(0x20, vec![0x00], vec![0x00]),
+            Transaction::write_read(0x20, vec![0x4C], vec![0x03, 0x00]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x03, 0x00]),
+            Transaction::write_read(0x20, vec![0x4C], vec![0x03, 0x00]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x00, 0x00]),
+        ]);
+        let dev: SharedDevice<_, NoopRawMutex> =
+            SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus)));
+
+        {
+            let mut pins = dev.split();
+            pins[0].set_edge_filter(EdgeFilter::Falling);
+            let mut events = dev.subscribe(0x0003).unwrap();
+            let mut wait = core::pin::pin!(pins[0].wait_for_any_edge());
+            assert!(poll_once(wait.as_mut()).await.is_pending());
+
+            // Pin 0 rising is dropped, pin 1 rising is reported
+            assert_eq!(dev.service_interrupt().await.unwrap(), 0x0003);
+            assert!(poll_once(wait.as_mut()).await.is_pending());
+            assert_eq!(
+                events.try_next().map(|event| (event.index(), event.edge)),
+                Some((1, Edge::Rising))
+            );
+            assert!(events.try_next().is_none());
+
+            dev.service_interrupt().await.unwrap();
+            assert!(poll_once(wait.as_mut()).await.is_ready());
+            assert_eq!(
+                events.try_next().map(|event| (event.index(), event.edge)),
+                Some((0, Edge::Falling))
+            );
+            assert_eq!(
+                events.try_next().map(|event| (event.index(), event.edge)),
+                Some((1, Edge::Falling))
+            );
+        }
+
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+
+    #[tokio::test]
+    async fn pulse_between_samples() {
+        let i2cbus = Mock::new(&[
+            Transaction::write_read(0x20, vec![0x00], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x00]),
+            // Pin 0_5 flagged but still low, pin 0_6 flagged and high
+            Transaction::write_read(0x20, vec![0x4C], vec![0x60, 0x00]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x40, 0x00]),
+        ]);
+        let dev: SharedDevice<_, NoopRawMutex> =
+            SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus)));
+
+        dev.sample_inputs().await.unwrap();
+        let mut events = dev.subscribe(u32::MAX).unwrap();
+        {
+            let mut pins = dev.split();
+            let mut wait = core::pin::pin!(pins[5].wait_for_falling_edge());
+            assert!(poll_once(wait.as_mut()).await.is_pending());
+            assert_eq!(dev.service_interrupt().await.unwrap(), 0x0060);
+            assert!(poll_once(wait.as_mut()).await.is_ready());
+        }
+
+        let pulse = events.try_next().unwrap();
+        assert_eq!(
+            (pulse.index(), pulse.kind, pulse.edge, pulse.level),
+            (5, EventKind::Pulse, Edge::Rising, false)
+        );
+        let edge = events.try_next().unwrap();
+        assert_eq!(
+            (edge.index(), edge.kind, edge.edge, edge.level),
+            (6, EventKind::Edge, Edge::Rising, true)
+        );
+        assert!(events.try_next().is_none());
+
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+
+    #[tokio::test]
+    async fn wait_for_any_pin() {
+        let i2cbus = Mock::new(&[
+            Transaction::write_read(0x20, vec![0x00], vec![0x00, 0x00]),
+            // Rising edge on an unwatched pin, then on pins 0_1 and 1_2
+            Transaction::write_read(0x20, vec![0x4C], vec![0x80, 0x00]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x80, 0x00]),
+            Transaction::write_read(0x20, vec![0x4C], vec![0x02, 0x04]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x82, 0x04]),
+        ]);
+        let dev: SharedDevice<_, NoopRawMutex> =
+            SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus)));
+
+        {
+            let mut wait = core::pin::pin!(dev.wait_for_any(0x0403, EdgeFilter::Rising));
+            assert!(poll_once(wait.as_mut()).await.is_pending());
+            dev.service_interrupt().await.unwrap();
+            assert!(poll_once(wait.as_mut()).await.is_pending());
+            dev.service_interrupt().await.unwrap();
+            assert_eq!(wait.await.unwrap(), 0x0402);
+        }
+
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+
+    #[tokio::test]
+    async fn waiters_beyond_the_slots_are_refused() {
+        let i2cbus = Mock::new(&vec![
+            Transaction::write_read(0x20, vec![0x00], vec![0x00, 0x00]);
+            ANY_WAITERS + 1
+        ]);
+        let dev: SharedDevice<_, NoopRawMutex> =
+            SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus)));
+
+        {
+            let mut waits: [_; ANY_WAITERS] =
+                core::array::from_fn(|_| Box::pin(dev.wait_for_any(0x0001, EdgeFilter::Both)));
+            for wait in &mut waits {
+                assert!(poll_once(wait.as_mut()).await.is_pending());
+            }
+            assert_eq!(
+                dev.wait_for_any(0x0001, EdgeFilter::Both).await,
+                Err(Pcal6416aError::TooManyWaiters)
+            );
+
+            drop(waits);
+            let mut wait = core::pin::pin!(dev.wait_for_any(0x0001, EdgeFilter::Both));
+            assert!(poll_once(wait.as_mut()).await.is_pending());
+        }
+
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+
+    /// Poll `future` once
+    async fn poll_once<F: Future>(mut future: core::pin::Pin<&mut F>) -> Poll<F::Output> {
+        poll_fn(|cx| Poll::Ready(future.as_mut().poll(cx))).await
+    }
+}
@@ -0,0 +1,410 @@
+//! Keypad matrix scanning.
+//!
+//! [`Keypad`] drives a key matrix of up to 8x8 keys from two ports: rows on one port as
+//! open-drain outputs, columns on the other as pulled-up inputs. A scan pulls one row low
+//! at a time and reads the column port, two register accesses per row. Scans are
+//! debounced by requiring consecutive identical results, and scans where three pressed
+//! keys could fake a fourth (ghosting, on matrices without diodes) are held back.
+//!
+//! With idling enabled, all rows are left low while no key is down and the keypad waits
+//! for a column to fall, so the bus stays quiet until a key is pressed. This needs the
+//! device to be serviced, e.g. by an [`crate::InterruptDispatcher`].
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embedded_hal_async::delay::DelayNs;
+
+use crate::demand::Waiter;
+use crate::interrupt::AnySlot;
+use crate::{Bank, Chip, Pcal6416aError, Port, SharedDevice};
+
+/// Default interval between two scans, in milliseconds
+pub const DEFAULT_SCAN_INTERVAL_MS: u32 = 10;
+
+/// Default number of identical scans for a result to be accepted
+pub const DEFAULT_DEBOUNCE_SCANS: u8 = 2;
+
+/// Position of a key in the matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Key {
+    /// Row, i.e. pin number on the row port
+    pub row: u8,
+    /// Column, i.e. pin number on the column port
+    pub column: u8,
+}
+
+/// Change reported by a [`Keypad`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum KeypadEvent {
+    /// A key went down
+    Down(Key),
+    /// A key went up
+    Up(Key),
+    /// The pressed keys cannot be told apart from ghosts; key states are held until the
+    /// ambiguity clears
+    Ghosting,
+}
+
+/// Key matrix on the ports of a [`SharedDevice`].
+///
+/// # Example
+/// ```ignore
+/// let mut keypad = Keypad::new(&shared, embassy_time::Delay, Port::Port0, Port::Port1)
+///     .with_lines(0x0F, 0x0F)
+///     .with_idle(true);
+/// keypad.init().await?;
+///
+/// loop {
+///     if let KeypadEvent::Down(key) = keypad.next_event().await? {
+///         handle(KEYMAP[usize::from(key.row)][usize::from(key.column)]);
+///     }
+/// }
+/// ```
+pub struct Keypad<'a, D: DelayNs, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> {
+    shared: &'a SharedDevice<I2c, M, C>,
+    delay: D,
+    rows: Port,
+    columns: Port,
+    row_mask: u8,
+    column_mask: u8,
+    scan_interval_ms: u32,
+    debounce_scans: u8,
+    idle: bool,
+    /// Last raw scan, one column mask per row
+    candidate: [u8; 8],
+    /// Consecutive scans equal to `candidate`
+    matches: u8,
+    /// Debounced key states
+    stable: [u8; 8],
+    /// Key states reported so far
+    reported: [u8; 8],
+    /// The accepted scan is ambiguous
+    ghosting: bool,
+    /// [`KeypadEvent::Ghosting`] was reported for the current ambiguity
+    ghost_reported: bool,
+}
+
+impl<'a, D: DelayNs, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> Keypad<'a, D, I2c, M, C> {
+    /// Create a keypad with rows on `rows` and columns on `columns`, using all 8 pins of
+    /// each port and timing scans with `delay`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rows` and `columns` are the same port.
+    pub fn new(shared: &'a SharedDevice<I2c, M, C>, delay: D, rows: Port, columns: Port) -> Self {
+        assert_ne!(rows, columns, "rows and columns must be on different ports");
+        Self {
+            shared,
+            delay,
+            rows,
+            columns,
+            row_mask: 0xFF,
+            column_mask: 0xFF,
+            scan_interval_ms: DEFAULT_SCAN_INTERVAL_MS,
+            debounce_scans: DEFAULT_DEBOUNCE_SCANS,
+            idle: false,
+            candidate: [0; 8],
+            matches: 0,
+            stable: [0; 8],
+            reported: [0; 8],
+            ghosting: false,
+            ghost_reported: false,
+        }
+    }
+
+    /// Restrict the matrix to the pins in `row_mask` and `column_mask`, leaving the
+    /// levels and directions of the other pins of both ports untouched
+    ///
+    /// Open-drain is set per port, so [`Keypad::init`] makes every output of the row
+    /// port open-drain, the pins outside `row_mask` included: keep push-pull outputs off
+    /// that port.
+    #[must_use]
+    pub const fn with_lines(mut self, row_mask: u8, column_mask: u8) -> Self {
+        self.row_mask = row_mask;
+        self.column_mask = column_mask;
+        self
+    }
+
+    /// Set the interval between two scans
+    #[must_use]
+    pub const fn with_scan_interval_ms(mut self, scan_interval_ms: u32) -> Self {
+        self.scan_interval_ms = scan_interval_ms;
+        self
+    }
+
+    /// Set the number of identical consecutive scans for a result to be accepted
+    #[must_use]
+    pub const fn with_debounce_scans(mut self, debounce_scans: u8) -> Self {
+        self.debounce_scans = debounce_scans;
+        self
+    }
+
+    /// Idle with all rows low while no key is down, waking on a column edge
+    #[must_use]
+    pub const fn with_idle(mut self, idle: bool) -> Self {
+        self.idle = idle;
+        self
+    }
+
+    /// Configure the pins: rows as open-drain outputs, columns as pulled-up inputs
+    ///
+    /// The whole row port is switched to open-drain, see [`Keypad::with_lines`].
+    ///
+    /// With idling enabled and demand masking disabled, the columns are also unmasked.
+    /// # Errors
+    ///
+    /// Will return `Err` if the part lacks agile I/O or underlying I2C bus operation fails
+    pub async fn init(&mut self) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let rows = u32::from(self.row_mask) << (self.rows.index() * 8);
+        let columns = u32::from(self.column_mask) << (self.columns.index() * 8);
+        let idle_rows = if self.idle { 0 } else { rows };
+
+        let mut device = self.shared.device.lock().await;
+        let output = device.read_bank_async(Bank::Output).await?;
+        device
+            .write_bank_async(Bank::Output, (output & !rows) | idle_rows)
+            .await?;
+        device.set_open_drain_async(self.rows, true).await?;
+        let select = device.read_bank_async(Bank::PullSelect).await?;
+        device.write_bank_async(Bank::PullSelect, select | columns).await?;
+        let enable = device.read_bank_async(Bank::PullEnable).await?;
+        device.write_bank_async(Bank::PullEnable, enable | columns).await?;
+        let config = device.read_bank_async(Bank::Configuration).await?;
+        device
+            .write_bank_async(Bank::Configuration, (config & !rows) | columns)
+            .await?;
+        if self.idle && !self.shared.demand_masking() {
+            let mask = device.read_bank_async(Bank::InterruptMask).await?;
+            device.write_bank_async(Bank::InterruptMask, mask & !columns).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Scan the matrix once, returning the pressed columns of each row
+    ///
+    /// The result is neither debounced nor checked for ghosting. The column levels follow
+    /// the row being driven, so they are kept out of the input snapshot: pin waiters and
+    /// event subscribers see no edges on the columns from scanning. The other pins of the
+    /// column port are merged into the snapshot as with any input read.
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn scan(&mut self) -> Result<[u8; 8], Pcal6416aError<I2c::Error>> {
+        let mut device = self.shared.device.lock().await;
+        let others = device.read_port_async(Bank::Output, self.rows).await? & !self.row_mask;
+        let mut pressed = [0; 8];
+        for (row, columns) in pressed.iter_mut().enumerate() {
+            if self.row_mask & (1 << row) == 0 {
+                continue;
+            }
+
+            device
+                .write_port_async(Bank::Output, self.rows, others | (self.row_mask & !(1 << row)))
+                .await?;
+            let value = device.read_port_async(Bank::Input, self.columns).await?;
+            let shift = self.columns.index() * 8;
+            let others = u32::from(!self.column_mask) << shift;
+            if others != 0 {
+                self.shared
+                    .record(&mut device, others, u32::from(value) << shift, 0)
+                    .await?;
+            }
+            *columns = !value & self.column_mask;
+        }
+
+        let idle_rows = if self.idle && pressed == [0; 8] {
+            0
+        } else {
+            self.row_mask
+        };
+        device
+            .write_port_async(Bank::Output, self.rows, others | idle_rows)
+            .await?;
+        Ok(pressed)
+    }
+
+    /// Wait for the next key change
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails, or
+    /// [`Pcal6416aError::TooManyWaiters`] if [`crate::ANY_WAITERS`] calls already wait on
+    /// the device
+    pub async fn next_event(&mut self) -> Result<KeypadEvent, Pcal6416aError<I2c::Error>> {
+        loop {
+            if let Some(event) = self.take_change() {
+                return Ok(event);
+            }
+            if self.ghosting && !self.ghost_reported {
+                self.ghost_reported = true;
+                return Ok(KeypadEvent::Ghosting);
+            }
+
+            let settled = self.matches >= self.debounce_scans && self.candidate == [0; 8];
+            if self.idle && settled {
+                self.wait_for_press().await?;
+            } else {
+                self.delay.delay_ms(self.scan_interval_ms).await;
+            }
+
+            let pressed = self.scan().await?;
+            self.accept(pressed);
+        }
+    }
+
+    /// Whether `key` is down, after debouncing
+    #[must_use]
+    pub fn is_down(&self, key: Key) -> bool {
+        key.row < 8 && key.column < 8 && self.stable[usize::from(key.row)] & (1 << key.column) != 0
+    }
+
+    /// Release the delay provider
+    pub fn into_inner(self) -> D {
+        self.delay
+    }
+
+    /// Merge a raw scan into the debounced state
+    fn accept(&mut self, pressed: [u8; 8]) {
+        if pressed == self.candidate {
+            self.matches = self.matches.saturating_add(1);
+        } else {
+            self.candidate = pressed;
+            self.matches = 1;
+        }
+        if self.matches < self.debounce_scans {
+            return;
+        }
+
+        if ghosted(pressed) {
+            if !self.ghosting {
+                self.ghosting = true;
+                self.ghost_reported = false;
+            }
+            return;
+        }
+
+        self.ghosting = false;
+        self.stable = pressed;
+    }
+
+    /// Report one difference between the debounced and reported states, releases first
+    #[allow(clippy::cast_possible_truncation)] // at most 8 rows and columns
+    fn take_change(&mut self) -> Option<KeypadEvent> {
+        for down in [false, true] {
+            for row in 0..8 {
+                let changed = if down {
+                    self.stable[row] & !self.reported[row]
+                } else {
+                    self.reported[row] & !self.stable[row]
+                };
+                if changed == 0 {
+                    continue;
+                }
+
+                let column = changed.trailing_zeros() as u8;
+                self.reported[row] ^= 1 << column;
+                let key = Key { row: row as u8, column };
+                return Some(if down {
+                    KeypadEvent::Down(key)
+                } else {
+                    KeypadEvent::Up(key)
+                });
+            }
+        }
+
+        None
+    }
+
+    /// With all rows low, wait for a column to fall
+    async fn wait_for_press(&self) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let columns = u32::from(self.column_mask) << (self.columns.index() * 8);
+        let slot = AnySlot::take(self.shared).ok_or(Pcal6416aError::TooManyWaiters)?;
+        let waiter = Waiter::new(self.shared, columns);
+        let levels = self.shared.sample_port(self.columns).await?;
+        if levels & self.column_mask == self.column_mask {
+            let since = self.shared.sequences();
+            self.shared
+                .wait_for_edges_since(&slot, columns, &since, (false, true))
+                .await;
+        }
+
+        waiter.release().await
+    }
+}
+
+/// Whether two rows share two pressed columns, so that one of the four keys may be a ghost
+fn ghosted(pressed: [u8; 8]) -> bool {
+    pressed.iter().enumerate().any(|(row, &columns)| {
+        pressed[row + 1..]
+            .iter()
+            .any(|&other| (columns & other).count_ones() >= 2)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, Transaction as DelayTransaction};
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use super::*;
+    use crate::{AddrPinState, Device, Pcal6416aDevice};
+
+    /// Transactions of a 2x2 scan, `columns` holding the column port read for each row
+    fn scan(columns: [u8; 2]) -> Vec<Transaction> {
+        vec![
+            Transaction::write_read(0x20, vec![0x02], vec![0x03]),
+            Transaction::write(0x20, vec![0x02, 0x02]),
+            Transaction::write_read(0x20, vec![0x01], vec![columns[0]]),
+            Transaction::write(0x20, vec![0x02, 0x01]),
+            Transaction::write_read(0x20, vec![0x01], vec![columns[1]]),
+            Transaction::write(0x20, vec![0x02, 0x03]),
+        ]
+    }
+
+    #[tokio::test]
+    async fn keys_and_ghosting() {
+        let mut expectations = vec![
+            Transaction::write_read(0x20, vec![0x02], vec![0x00, 0x00]),
+            Transaction::write(0x20, vec![0x02, 0x03, 0x00]),
+            Transaction::write_read(0x20, vec![0x4F], vec![0x00]),
+            Transaction::write(0x20, vec![0x4F, 0x01]),
+            Transaction::write_read(0x20, vec![0x48], vec![0x00, 0x00]),
+            Transaction::write(0x20, vec![0x48, 0x00, 0x03]),
+            Transaction::write_read(0x20, vec![0x46], vec![0x00, 0x00]),
+            Transaction::write(0x20, vec![0x46, 0x00, 0x03]),
+            Transaction::write_read(0x20, vec![0x06], vec![0xFF, 0xFF]),
+            Transaction::write(0x20, vec![0x06, 0xFC, 0xFF]),
+        ];
+        // Key 1/0 down, then a bouncing scan, then three keys down faking the fourth
+        for columns in [[0xFF, 0xFE], [0xFF, 0xFE], [0xFF, 0xFC], [0xFC, 0xFC], [0xFC, 0xFC]] {
+            expectations.extend(scan(columns));
+        }
+        // Everything released
+        for columns in [[0xFF, 0xFF], [0xFF, 0xFF]] {
+            expectations.extend(scan(columns));
+        }
+        let delay = CheckedDelay::new(&vec![DelayTransaction::async_delay_ms(10); 7]);
+        let dev: SharedDevice<_, NoopRawMutex> = SharedDevice::new(Device::new(Pcal6416aDevice::new(
+            AddrPinState::Low,
+            Mock::new(&expectations),
+        )));
+
+        let mut keypad = Keypad::new(&dev, delay, Port::Port0, Port::Port1).with_lines(0x03, 0x03);
+        keypad.init().await.unwrap();
+
+        let key = Key { row: 1, column: 0 };
+        assert_eq!(keypad.next_event().await.unwrap(), KeypadEvent::Down(key));
+        assert!(keypad.is_down(key));
+        assert!(!keypad.is_down(Key { row: 1, column: 8 }));
+        assert_eq!(keypad.next_event().await.unwrap(), KeypadEvent::Ghosting);
+        assert!(keypad.is_down(key));
+        assert_eq!(keypad.next_event().await.unwrap(), KeypadEvent::Up(key));
+        // Scans leave no edges on the columns
+        assert_eq!(dev.with_events(|events| events.sequence(8)), (0, 0));
+
+        keypad.into_inner().done();
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+}
@@ -0,0 +1,471 @@
+//! Status LEDs and blink patterns.
+//!
+//! [`Led`] wraps an output [`IoPin`], hiding whether the LED is wired active-low.
+//! [`LedPatterns`] runs a [`Pattern`] per LED from a single task; LEDs changing at the
+//! same instant are updated with one write of the output registers, so patterns stay in
+//! phase and the bus sees one access per change rather than one per LED.
+
+use core::cell::RefCell;
+use core::str::Chars;
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::signal::Signal;
+use embedded_hal_async::delay::DelayNs;
+
+use crate::{Chip, IoPin, Pcal6416aError, SharedDevice};
+
+/// Default longest single delay of a [`LedPatterns`] task, in milliseconds
+pub const DEFAULT_PATTERN_RESOLUTION_MS: u32 = 50;
+
+/// LED on an expander output.
+pub struct Led<'a, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> {
+    pin: IoPin<'a, I2c, M, C>,
+    active_low: bool,
+}
+
+impl<'a, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> Led<'a, I2c, M, C> {
+    /// Create an LED lit by driving `pin` high
+    #[must_use]
+    pub const fn new(pin: IoPin<'a, I2c, M, C>) -> Self {
+        Self { pin, active_low: false }
+    }
+
+    /// Select whether the LED is lit by driving its pin low
+    #[must_use]
+    pub const fn with_active_low(mut self, active_low: bool) -> Self {
+        self.active_low = active_low;
+        self
+    }
+
+    /// Whether the LED is lit by driving its pin low
+    #[must_use]
+    pub const fn active_low(&self) -> bool {
+        self.active_low
+    }
+
+    /// Get the pin driving the LED
+    #[must_use]
+    pub const fn pin(&self) -> &IoPin<'a, I2c, M, C> {
+        &self.pin
+    }
+
+    /// Turn the LED off and configure its pin as an output
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn init(&self) -> Result<(), Pcal6416aError<I2c::Error>> {
+        self.set(false).await?;
+        self.pin.set_as_output_async().await
+    }
+
+    /// Turn the LED on or off
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn set(&self, on: bool) -> Result<(), Pcal6416aError<I2c::Error>> {
+        if on == self.active_low {
+            self.pin.set_low_async().await
+        } else {
+            self.pin.set_high_async().await
+        }
+    }
+
+    /// Turn the LED on
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn on(&self) -> Result<(), Pcal6416aError<I2c::Error>> {
+        self.set(true).await
+    }
+
+    /// Turn the LED off
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn off(&self) -> Result<(), Pcal6416aError<I2c::Error>> {
+        self.set(false).await
+    }
+
+    /// Toggle the LED
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn toggle(&self) -> Result<(), Pcal6416aError<I2c::Error>> {
+        self.pin.toggle_async().await
+    }
+
+    /// Whether the LED is on, from the output register
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn is_on(&self) -> Result<bool, Pcal6416aError<I2c::Error>> {
+        Ok(self.pin.is_set_high_async().await? != self.active_low)
+    }
+
+    /// Release the pin
+    #[must_use]
+    pub fn into_inner(self) -> IoPin<'a, I2c, M, C> {
+        self.pin
+    }
+}
+
+/// Repeating light pattern for a [`LedPatterns`] task
+///
+/// Step durations are at least 1 ms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Pattern {
+    /// Steady off
+    Off,
+    /// Steady on
+    On,
+    /// On for `on_ms`, then off for `off_ms`
+    Blink {
+        /// Time on, in milliseconds
+        on_ms: u32,
+        /// Time off, in milliseconds
+        off_ms: u32,
+    },
+    /// Two short flashes every `period_ms`
+    Heartbeat {
+        /// Length of one beat, in milliseconds
+        period_ms: u32,
+    },
+    /// `text` in Morse code, followed by a word gap; letters and digits are sent,
+    /// spaces separate words and anything else is skipped
+    Morse {
+        /// Text to send
+        text: &'static str,
+        /// Length of a dot, in milliseconds
+        unit_ms: u32,
+    },
+    /// Alternating on and off durations in milliseconds, starting on
+    Steps(&'static [u32]),
+}
+
+impl Pattern {
+    /// Level and duration of step `n`, `None` past the last step
+    ///
+    /// Steady patterns have a single step without duration.
+    fn step(self, n: usize) -> Option<(bool, Option<u32>)> {
+        let timed = |(on, ms): (bool, u32)| (on, Some(ms.max(1)));
+        match self {
+            Self::Off => (n == 0).then_some((false, None)),
+            Self::On => (n == 0).then_some((true, None)),
+            Self::Blink { on_ms, off_ms } => [(true, on_ms), (false, off_ms)].get(n).copied().map(timed),
+            Self::Heartbeat { period_ms } => {
+                let beat = period_ms / 10;
+                [(true, beat), (false, beat), (true, beat), (false, period_ms - 3 * beat)]
+                    .get(n)
+                    .copied()
+                    .map(timed)
+            }
+            Self::Morse { text, unit_ms } => MorseSteps::new(text)
+                .nth(n)
+                .map(|(on, units)| timed((on, units.saturating_mul(unit_ms)))),
+            Self::Steps(steps) => steps.get(n).map(|&ms| timed((n.is_multiple_of(2), ms))),
+        }
+    }
+}
+
+/// Dot and dash codes of the letters A-Z, then the digits 0-9
+const MORSE: [&str; 36] = [
+    ".-", "-...", "-.-.", "-..", ".", "..-.", "--.", "....", "..", ".---", "-.-", ".-..", "--", "-.", "---", ".--.",
+    "--.-", ".-.", "...", "-", "..-", "...-", ".--", "-..-", "-.--", "--..", "-----", ".----", "..---", "...--",
+    "....-", ".....", "-....", "--...", "---..", "----.",
+];
+
+/// Steps of a Morse message, as (on, length in dot units)
+struct MorseSteps<'t> {
+    chars: Chars<'t>,
+    /// Elements left of the current character
+    code: &'static [u8],
+    /// Gap owed before the next element
+    gap: u32,
+    done: bool,
+}
+
+impl<'t> MorseSteps<'t> {
+    fn new(text: &'t str) -> Self {
+        Self {
+            chars: text.chars(),
+            code: &[],
+            gap: 0,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for MorseSteps<'_> {
+    type Item = (bool, u32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((&element, rest)) = self.code.split_first() {
+                if self.gap > 0 {
+                    return Some((false, core::mem::take(&mut self.gap)));
+                }
+                self.code = rest;
+                self.gap = 1;
+                return Some((true, if element == b'-' { 3 } else { 1 }));
+            }
+
+            match self.chars.next() {
+                Some(' ') if self.gap > 0 => self.gap = 7,
+                Some(c) => {
+                    let code = match c.to_ascii_uppercase() {
+                        letter @ 'A'..='Z' => MORSE[letter as usize - 'A' as usize],
+                        digit @ '0'..='9' => MORSE[26 + digit as usize - '0' as usize],
+                        _ => continue,
+                    };
+                    if self.gap > 0 {
+                        self.gap = self.gap.max(3);
+                    }
+                    self.code = code.as_bytes();
+                }
+                None if self.done => return None,
+                None => {
+                    self.done = true;
+                    self.gap = 0;
+                    return Some((false, 7));
+                }
+            }
+        }
+    }
+}
+
+/// Progress of one LED through its pattern
+#[derive(Clone, Copy)]
+struct Slot {
+    pattern: Pattern,
+    step: usize,
+    /// Time left in the current step, `None` when steady
+    remaining: Option<u32>,
+    /// The pattern changed and starts over
+    restart: bool,
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            pattern: Pattern::Off,
+            step: 0,
+            remaining: None,
+            restart: true,
+        }
+    }
+
+    /// Let `elapsed_ms` pass, returning the new level if a step started
+    fn advance(&mut self, elapsed_ms: u32) -> Option<bool> {
+        if core::mem::take(&mut self.restart) {
+            self.step = 0;
+            return Some(self.start());
+        }
+
+        let remaining = self.remaining?.saturating_sub(elapsed_ms);
+        if remaining > 0 {
+            self.remaining = Some(remaining);
+            return None;
+        }
+
+        self.step += 1;
+        Some(self.start())
+    }
+
+    /// Enter the current step, wrapping around after the last one
+    fn start(&mut self) -> bool {
+        let (on, remaining) = self.pattern.step(self.step).unwrap_or_else(|| {
+            self.step = 0;
+            self.pattern.step(0).unwrap_or((false, None))
+        });
+        self.remaining = remaining;
+        on
+    }
+}
+
+/// Pattern runner for `N` LEDs of one [`SharedDevice`].
+///
+/// # Example
+/// ```ignore
+/// let [red, green, ..] = shared.split();
+/// let leds = LedPatterns::new(&shared, [Led::new(red).with_active_low(true), Led::new(green)]);
+///
+/// // In a dedicated task
+/// leds.run(embassy_time::Delay).await?;
+///
+/// // Anywhere else
+/// leds.set_pattern(0, Pattern::Morse { text: "SOS", unit_ms: 150 });
+/// leds.set_pattern(1, Pattern::Heartbeat { period_ms: 1000 });
+/// ```
+pub struct LedPatterns<'a, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip, const N: usize> {
+    shared: &'a SharedDevice<I2c, M, C>,
+    leds: [Led<'a, I2c, M, C>; N],
+    slots: embassy_sync::blocking_mutex::Mutex<M, RefCell<[Slot; N]>>,
+    changed: Signal<M, ()>,
+    resolution_ms: u32,
+}
+
+impl<'a, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip, const N: usize> LedPatterns<'a, I2c, M, C, N> {
+    /// Create a runner for `leds`, all starting [`Pattern::Off`]
+    ///
+    /// The LEDs' pins have to be configured as outputs, see [`Led::init`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of `leds` belongs to another device than `shared`.
+    pub fn new(shared: &'a SharedDevice<I2c, M, C>, leds: [Led<'a, I2c, M, C>; N]) -> Self {
+        assert!(
+            leds.iter().all(|led| core::ptr::eq(led.pin.shared, shared)),
+            "LEDs must be on the shared device"
+        );
+        Self {
+            shared,
+            leds,
+            slots: embassy_sync::blocking_mutex::Mutex::new(RefCell::new([const { Slot::new() }; N])),
+            changed: Signal::new(),
+            resolution_ms: DEFAULT_PATTERN_RESOLUTION_MS,
+        }
+    }
+
+    /// Set the longest single delay while patterns run
+    ///
+    /// A pattern change takes effect after at most this long.
+    #[must_use]
+    pub const fn with_resolution_ms(mut self, resolution_ms: u32) -> Self {
+        self.resolution_ms = resolution_ms;
+        self
+    }
+
+    /// Start `pattern` on LED `led`, from its first step
+    ///
+    /// # Panics
+    ///
+    /// Panics if `led` is not below `N`.
+    pub fn set_pattern(&self, led: usize, pattern: Pattern) {
+        self.slots.lock(|slots| {
+            let slot = &mut slots.borrow_mut()[led];
+            slot.pattern = pattern;
+            slot.restart = true;
+        });
+        self.changed.signal(());
+    }
+
+    /// Get the pattern of LED `led`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `led` is not below `N`.
+    #[must_use]
+    pub fn pattern(&self, led: usize) -> Pattern {
+        self.slots.lock(|slots| slots.borrow()[led].pattern)
+    }
+
+    /// Get the LEDs
+    #[must_use]
+    pub const fn leds(&self) -> &[Led<'a, I2c, M, C>; N] {
+        &self.leds
+    }
+
+    /// Run the patterns forever, timing them with `delay`
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn run<D: DelayNs>(&self, mut delay: D) -> Result<core::convert::Infallible, Pcal6416aError<I2c::Error>> {
+        let mut elapsed_ms = 0;
+        loop {
+            let (mask, values, next) = self.slots.lock(|slots| {
+                let (mut mask, mut values, mut next) = (0, 0, None::<u32>);
+                for (led, slot) in self.leds.iter().zip(slots.borrow_mut().iter_mut()) {
+                    if let Some(on) = slot.advance(elapsed_ms) {
+                        let bit = 1 << led.pin.index();
+                        mask |= bit;
+                        if on != led.active_low {
+                            values |= bit;
+                        }
+                    }
+                    if let Some(remaining) = slot.remaining {
+                        next = Some(next.map_or(remaining, |next| next.min(remaining)));
+                    }
+                }
+                (mask, values, next)
+            });
+
+            if mask != 0 {
+                self.shared.write_outputs(mask, values).await?;
+            }
+
+            if let Some(next) = next {
+                elapsed_ms = next.min(self.resolution_ms);
+                delay.delay_ms(elapsed_ms).await;
+            } else {
+                self.changed.wait().await;
+                elapsed_ms = 0;
+            }
+        }
+    }
+
+    /// Release the LEDs
+    pub fn into_inner(self) -> [Led<'a, I2c, M, C>; N] {
+        self.leds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, Transaction as DelayTransaction};
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use super::*;
+    use crate::{AddrPinState, Device, Pcal6416aDevice};
+
+    #[test]
+    fn morse_timing() {
+        let steps: Vec<_> = MorseSteps::new("e t.i").collect();
+        assert_eq!(
+            steps,
+            [
+                (true, 1),
+                (false, 7),
+                (true, 3),
+                (false, 3),
+                (true, 1),
+                (false, 1),
+                (true, 1),
+                (false, 7)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn simultaneous_changes_share_a_write() {
+        let i2cbus = Mock::new(&[
+            Transaction::write_read(0x20, vec![0x02], vec![0x00, 0x00]),
+            Transaction::write(0x20, vec![0x02, 0x01, 0x00]),
+            Transaction::write_read(0x20, vec![0x02], vec![0x01, 0x00]),
+            Transaction::write(0x20, vec![0x02, 0x02, 0x00]),
+            Transaction::write_read(0x20, vec![0x02], vec![0x02, 0x00]),
+            Transaction::write(0x20, vec![0x02, 0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x02], vec![0x00, 0x00]).with_error(embedded_hal::i2c::ErrorKind::Other),
+        ]);
+        let delay = CheckedDelay::new(&[
+            DelayTransaction::async_delay_ms(10),
+            DelayTransaction::async_delay_ms(10),
+            DelayTransaction::async_delay_ms(10),
+        ]);
+        let dev: SharedDevice<_, NoopRawMutex> =
+            SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus)));
+
+        let [first, second, ..] = dev.split();
+        let leds = LedPatterns::new(&dev, [Led::new(first), Led::new(second).with_active_low(true)]);
+        leds.set_pattern(0, Pattern::Blink { on_ms: 10, off_ms: 20 });
+        leds.set_pattern(1, Pattern::Steps(&[10, 10]));
+
+        let mut delay_handle = delay.clone();
+        assert!(leds.run(delay).await.is_err());
+        assert_eq!(leds.pattern(1), Pattern::Steps(&[10, 10]));
+
+        delay_handle.done();
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+}
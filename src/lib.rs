@@ -12,11 +12,74 @@
 #![cfg_attr(not(test), no_std)]
 #![allow(missing_docs)]
 
+mod aggregate;
+mod button;
+mod chip;
+mod counter;
+mod debounce;
+mod demand;
+mod encoder;
+mod event;
+mod failsafe;
+mod interlock;
+mod interrupt;
+mod keypad;
+mod led;
+mod mux;
+mod park;
+mod poll;
+mod power;
+mod protect;
+mod pulse;
+mod storm;
+mod timeout;
+
+use core::cell::RefCell;
+use core::marker::PhantomData;
+
+pub use aggregate::MultiDevice;
+pub use button::{Button, ButtonConfig, ButtonEvent};
+pub use chip::{Bank, Chip, Pcal6408a, Pcal6416a, Pcal6524};
+pub use counter::EdgeCount;
+pub use debounce::{DEFAULT_SETTLE_MS, Debounced};
+pub use encoder::{DEFAULT_STEPS_PER_DETENT, Direction, Encoder};
+use event::EventChannel;
+pub use event::{EVENT_QUEUE_DEPTH, EVENT_SUBSCRIBERS, EventKind, PinEvent, PinEvents};
+use failsafe::FailSafe;
+pub use failsafe::{SafeLevel, SafeState, SafeStateGuard};
+use interlock::Interlocks;
+pub use interlock::{Interlock, InterlockRule, MAX_INTERLOCKS};
+use interrupt::EventState;
+pub use interrupt::{ANY_WAITERS, Edge, EdgeFilter, InterruptDispatcher, InterruptError};
+pub use keypad::{DEFAULT_DEBOUNCE_SCANS, DEFAULT_SCAN_INTERVAL_MS, Key, Keypad, KeypadEvent};
+pub use led::{DEFAULT_PATTERN_RESOLUTION_MS, Led, LedPatterns, Pattern};
+pub use mux::{I2cMux, MUX_CHANNELS, MuxChannel};
+pub use park::ParkConfig;
+use park::Snapshot;
+pub use poll::{DEFAULT_POLL_INTERVAL_MS, InputPoller};
+pub use power::{DEFAULT_POWER_POLL_INTERVAL_MS, PowerError, PowerFailure, PowerSequence, PowerStep};
+use protect::Protection;
+pub use pulse::OneShot;
+pub use storm::StormConfig;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Pcal6416aError<E> {
     /// I2C bus error
     I2c(E),
+    /// The register or operation is not available on the configured device variant or chip
+    Unsupported,
+    /// The write would violate an output [`Interlock`]
+    ///
+    /// Nothing was written, unless the write was a break-before-make switch-over within the
+    /// dead time of its group: then the pins leaving the group high were driven low, every
+    /// other output kept its previous value, and retrying the same write after the dead
+    /// time completes it.
+    Interlock,
+    /// The write would change a locked pin or register, see [`Pcal6416aDevice::lock_pins`]
+    Locked,
+    /// All [`ANY_WAITERS`] wait slots of the device are taken
+    TooManyWaiters,
 }
 
 impl<E: core::fmt::Debug> embedded_hal::digital::Error for Pcal6416aError<E> {
@@ -27,9 +90,13 @@ impl<E: core::fmt::Debug> embedded_hal::digital::Error for Pcal6416aError<E> {
 
 const IOEXP_ADDR_LOW: u8 = 0x20;
 const IOEXP_ADDR_HIGH: u8 = 0x21;
-const LARGEST_REG_SIZE_BYTES: usize = 2;
+/// A full bank of the 24-bit PCAL6524 is the largest single register write
+const LARGEST_REG_SIZE_BYTES: usize = 3;
+/// First register of the agile I/O block (0x40-0x4F), absent on the non-"L" parts
+const AGILE_IO_BASE: u8 = 0x40;
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum AddrPinState {
     High,
     Low,
@@ -45,9 +112,210 @@ impl AddrPinState {
     }
 }
 
-pub struct Pcal6416aDevice<I2c> {
+/// I2C address of the expander.
+///
+/// Usually derived from the ADDR strap, but an explicit 7-bit address can be given when the
+/// expander sits behind an address translator (e.g. LTC4316) or on a bus that remaps addresses.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Address {
+    /// Address selected by the ADDR pin, as defined by the [`Chip`]
+    Pin(AddrPinState),
+    /// Explicit 7-bit address
+    Explicit(ExplicitAddress),
+}
+
+/// 7-bit I2C address, checked when created
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ExplicitAddress(u8);
+
+/// Error returned for an I2C address that does not fit in 7 bits
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InvalidAddress(pub u8);
+
+impl ExplicitAddress {
+    /// Create an address, `None` if `address` does not fit in 7 bits
+    #[must_use]
+    pub const fn new(address: u8) -> Option<Self> {
+        if address <= 0x7F { Some(Self(address)) } else { None }
+    }
+
+    /// Get the 7-bit address
+    #[must_use]
+    pub const fn get(self) -> u8 {
+        self.0
+    }
+}
+
+impl TryFrom<u8> for ExplicitAddress {
+    type Error = InvalidAddress;
+
+    fn try_from(address: u8) -> Result<Self, Self::Error> {
+        Self::new(address).ok_or(InvalidAddress(address))
+    }
+}
+
+impl Address {
+    /// Create an explicit address, `None` if `address` does not fit in 7 bits
+    #[must_use]
+    pub const fn explicit(address: u8) -> Option<Self> {
+        match ExplicitAddress::new(address) {
+            Some(address) => Some(Self::Explicit(address)),
+            None => None,
+        }
+    }
+
+    /// Resolve the 7-bit address for the chip `C`
+    #[must_use]
+    pub fn resolve<C: Chip>(self) -> u8 {
+        match self {
+            Self::Pin(addr_pin) => C::address(addr_pin),
+            Self::Explicit(address) => address.get(),
+        }
+    }
+}
+
+impl From<AddrPinState> for Address {
+    fn from(addr_pin: AddrPinState) -> Self {
+        Self::Pin(addr_pin)
+    }
+}
+
+impl From<ExplicitAddress> for Address {
+    fn from(address: ExplicitAddress) -> Self {
+        Self::Explicit(address)
+    }
+}
+
+impl TryFrom<u8> for Address {
+    type Error = InvalidAddress;
+
+    fn try_from(address: u8) -> Result<Self, Self::Error> {
+        ExplicitAddress::try_from(address).map(Self::Explicit)
+    }
+}
+
+/// Register-compatible part fitted at the expander footprint.
+///
+/// The PCA6416A and TCA6416 share registers 0x00-0x07 with the PCAL6416A but lack the
+/// agile I/O block (pull resistors, drive strength, input latch and interrupt mask).
+/// Accesses to that block on those parts fail with [`Pcal6416aError::Unsupported`]
+/// without touching the bus.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Variant {
+    /// NXP PCAL6416A, with agile I/O
+    #[default]
+    Pcal6416a,
+    /// NXP PCA6416A
+    Pca6416a,
+    /// TI TCA6416
+    Tca6416,
+}
+
+impl Variant {
+    /// Whether the part implements the agile I/O registers at 0x40-0x4F
+    #[must_use]
+    pub const fn has_agile_io(self) -> bool {
+        matches!(self, Self::Pcal6416a)
+    }
+
+    const fn supports(self, address: u8) -> bool {
+        address < AGILE_IO_BASE || self.has_agile_io()
+    }
+}
+
+/// I2C register interface for an expander of the agile I/O family.
+///
+/// The chip parameter defaults to the PCAL6416A. The typed register accessors generated
+/// from `device.yaml` follow the PCAL6416A register map; for other chips use the pin- and
+/// bank-level methods on [`Device`], which go through the [`Chip`] description.
+pub struct Pcal6416aDevice<I2c, C = Pcal6416a> {
+    /// ADDR pin strap, ignored when the interface was created with an explicit address
     pub addr_pin: AddrPinState,
     pub i2cbus: I2c,
+    /// Explicit address overriding the strap
+    explicit: Option<ExplicitAddress>,
+    variant: Variant,
+    interlocks: Interlocks,
+    protection: Protection,
+    fail_safe: FailSafe,
+    /// Registers saved while parked
+    parked: Option<Snapshot>,
+    chip: PhantomData<C>,
+}
+
+impl<I2c> Pcal6416aDevice<I2c> {
+    /// Create a register interface for a PCAL6416A.
+    ///
+    /// `address` is either the [`AddrPinState`] strap or an explicit 7-bit address.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let device = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
+    /// // Behind an address translator
+    /// let device = Device::new(Pcal6416aDevice::new(Address::try_from(0x52)?, i2cbus));
+    /// ```
+    pub fn new(address: impl Into<Address>, i2cbus: I2c) -> Self {
+        Self::new_for_chip(address, i2cbus)
+    }
+
+    /// Select the part variant fitted, restricting register access accordingly.
+    #[must_use]
+    pub const fn with_variant(mut self, variant: Variant) -> Self {
+        self.variant = variant;
+        self
+    }
+}
+
+impl<I2c, C: Chip> Pcal6416aDevice<I2c, C> {
+    /// Create a register interface for the chip `C`.
+    ///
+    /// `address` is either the [`AddrPinState`] strap or an explicit 7-bit address.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let device = Device::new(Pcal6416aDevice::<_, Pcal6408a>::new_for_chip(AddrPinState::Low, i2cbus));
+    /// ```
+    pub fn new_for_chip(address: impl Into<Address>, i2cbus: I2c) -> Self {
+        let (addr_pin, explicit) = match address.into() {
+            Address::Pin(addr_pin) => (addr_pin, None),
+            Address::Explicit(address) => (AddrPinState::Low, Some(address)),
+        };
+        Self {
+            addr_pin,
+            i2cbus,
+            explicit,
+            variant: Variant::Pcal6416a,
+            interlocks: Interlocks::new(),
+            protection: Protection::new(),
+            fail_safe: FailSafe::new(),
+            parked: None,
+            chip: PhantomData,
+        }
+    }
+
+    /// Get the I2C address of the expander
+    #[must_use]
+    pub fn address(&self) -> Address {
+        self.explicit.map_or(Address::Pin(self.addr_pin), Address::Explicit)
+    }
+
+    /// Get the part variant fitted
+    #[must_use]
+    pub const fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    fn i2c_address(&self) -> u8 {
+        self.address().resolve::<C>()
+    }
+
+    fn supports(&self, address: u8) -> bool {
+        self.variant.supports(address & !C::AUTO_INCREMENT)
+    }
 }
 
 device_driver::create_device!(
@@ -61,34 +329,86 @@ device_driver::create_device!(
 /// multiple async tasks to access the same PCAL6416A device concurrently with
 /// synchronized I2C register access. Use [`SharedDevice::split`] to obtain
 /// individual [`IoPin`] instances that can be passed to different tasks.
-pub struct SharedDevice<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::RawMutex> {
-    device: embassy_sync::mutex::Mutex<M, Device<Pcal6416aDevice<I2c>>>,
+pub struct SharedDevice<
+    I2c: embedded_hal_async::i2c::I2c,
+    M: embassy_sync::blocking_mutex::raw::RawMutex,
+    C: Chip = Pcal6416a,
+> {
+    device: embassy_sync::mutex::Mutex<M, Device<Pcal6416aDevice<I2c, C>>>,
+    events: embassy_sync::blocking_mutex::Mutex<M, RefCell<EventState>>,
+    channel: EventChannel<M>,
 }
 
-impl<I2c: embedded_hal_async::i2c::I2c> device_driver::AsyncRegisterInterface for Pcal6416aDevice<I2c> {
+impl<I2c: embedded_hal_async::i2c::I2c, C: Chip> Pcal6416aDevice<I2c, C> {
+    /// Write `data` at `address` without any check
+    async fn transfer_write_async(&mut self, address: u8, data: &[u8]) -> Result<(), Pcal6416aError<I2c::Error>> {
+        // Add one byte for register address
+        let mut buf = [0u8; 1 + LARGEST_REG_SIZE_BYTES];
+        buf[0] = address;
+        buf[1..=data.len()].copy_from_slice(data);
+
+        // Because the pcal6416a has a mix of 1 byte and 2 byte registers that can be written to,
+        // we pass in a slice of the appropriate size so we do not accidentally write to the register at
+        // address + 1 when writing to a 1 byte register
+        let result = self.i2cbus.write(self.i2c_address(), &buf[..=data.len()]).await;
+        self.track_async(result).await
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, C: Chip> device_driver::AsyncRegisterInterface for Pcal6416aDevice<I2c, C> {
     type Error = Pcal6416aError<I2c::Error>;
     type AddressType = u8;
 
     async fn write_register(
         &mut self,
         address: Self::AddressType,
-        _size_bits: u32,
+        size_bits: u32,
         data: &[u8],
     ) -> Result<(), Self::Error> {
         assert!((data.len() <= LARGEST_REG_SIZE_BYTES), "Register size too big");
 
-        // Add one byte for register address
-        let mut buf = [0u8; 1 + LARGEST_REG_SIZE_BYTES];
-        buf[0] = address;
-        buf[1..=data.len()].copy_from_slice(data);
+        if !self.supports(address) {
+            return Err(Pcal6416aError::Unsupported);
+        }
 
-        // Because the pcal6416a has a mix of 1 byte and 2 byte registers that can be written to,
-        // we pass in a slice of the appropriate size so we do not accidentally write to the register at
-        // address + 1 when writing to a 1 byte register
-        self.i2cbus
-            .write(self.addr_pin.address(), &buf[..=data.len()])
-            .await
-            .map_err(Pcal6416aError::I2c)
+        if self.protection.protects::<C>(address, data.len()) {
+            let mut current = [0u8; LARGEST_REG_SIZE_BYTES];
+            device_driver::AsyncRegisterInterface::read_register(self, address, size_bits, &mut current[..data.len()])
+                .await?;
+            if !self.protection.allows::<C>(address, &current[..data.len()], data) {
+                return Err(Pcal6416aError::Locked);
+            }
+        }
+
+        let (mask, values) = interlock::output_bits::<C>(address, data);
+        if mask != 0 {
+            if self.interlocks.missing(mask) != 0 {
+                let mut outputs = [0u8; LARGEST_REG_SIZE_BYTES];
+                device_driver::AsyncRegisterInterface::read_register(
+                    self,
+                    bank_register::<C>(Bank::Output),
+                    bank_bits::<C>(),
+                    &mut outputs[..C::PORTS],
+                )
+                .await?;
+            }
+
+            let (first, allowed) = self.interlocks.check(mask, values);
+            if let Some(first) = first {
+                let mut broken = [0u8; LARGEST_REG_SIZE_BYTES];
+                broken[..data.len()].copy_from_slice(data);
+                interlock::set_output_bits::<C>(address, &mut broken[..data.len()], first);
+                self.transfer_write_async(address, &broken[..data.len()]).await?;
+                self.interlocks.commit(mask, first);
+            }
+            if !allowed {
+                return Err(Pcal6416aError::Interlock);
+            }
+        }
+
+        self.transfer_write_async(address, data).await?;
+        self.interlocks.commit(mask, values);
+        Ok(())
     }
 
     async fn read_register(
@@ -97,20 +417,22 @@ impl<I2c: embedded_hal_async::i2c::I2c> device_driver::AsyncRegisterInterface fo
         _size_bits: u32,
         data: &mut [u8],
     ) -> Result<(), Self::Error> {
-        self.i2cbus
-            .write_read(self.addr_pin.address(), &[address], data)
-            .await
-            .map_err(Pcal6416aError::I2c)
-    }
-}
+        if !self.supports(address) {
+            return Err(Pcal6416aError::Unsupported);
+        }
 
-impl<I2c: embedded_hal::i2c::I2c> device_driver::RegisterInterface for Pcal6416aDevice<I2c> {
-    type Error = Pcal6416aError<I2c::Error>;
-    type AddressType = u8;
+        let result = self.i2cbus.write_read(self.i2c_address(), &[address], data).await;
+        self.track_async(result).await?;
 
-    fn write_register(&mut self, address: Self::AddressType, _size_bits: u32, data: &[u8]) -> Result<(), Self::Error> {
-        assert!((data.len() <= LARGEST_REG_SIZE_BYTES), "Register size too big");
+        let (mask, values) = interlock::output_bits::<C>(address, data);
+        self.interlocks.observe(mask, values);
+        Ok(())
+    }
+}
 
+impl<I2c: embedded_hal::i2c::I2c, C: Chip> Pcal6416aDevice<I2c, C> {
+    /// Write `data` at `address` without any check
+    fn transfer_write(&mut self, address: u8, data: &[u8]) -> Result<(), Pcal6416aError<I2c::Error>> {
         // Add one byte for register address
         let mut buf = [0u8; 1 + LARGEST_REG_SIZE_BYTES];
         buf[0] = address;
@@ -119,9 +441,58 @@ impl<I2c: embedded_hal::i2c::I2c> device_driver::RegisterInterface for Pcal6416a
         // Because the pcal6416a has a mix of 1 byte and 2 byte registers that can be written to,
         // we pass in a slice of the appropriate size so we do not accidentally write to the register at
         // address + 1 when writing to a 1 byte register
-        self.i2cbus
-            .write(self.addr_pin.address(), &buf[..=data.len()])
-            .map_err(Pcal6416aError::I2c)
+        let result = self.i2cbus.write(self.i2c_address(), &buf[..=data.len()]);
+        self.track(result)
+    }
+}
+
+impl<I2c: embedded_hal::i2c::I2c, C: Chip> device_driver::RegisterInterface for Pcal6416aDevice<I2c, C> {
+    type Error = Pcal6416aError<I2c::Error>;
+    type AddressType = u8;
+
+    fn write_register(&mut self, address: Self::AddressType, size_bits: u32, data: &[u8]) -> Result<(), Self::Error> {
+        assert!((data.len() <= LARGEST_REG_SIZE_BYTES), "Register size too big");
+
+        if !self.supports(address) {
+            return Err(Pcal6416aError::Unsupported);
+        }
+
+        if self.protection.protects::<C>(address, data.len()) {
+            let mut current = [0u8; LARGEST_REG_SIZE_BYTES];
+            device_driver::RegisterInterface::read_register(self, address, size_bits, &mut current[..data.len()])?;
+            if !self.protection.allows::<C>(address, &current[..data.len()], data) {
+                return Err(Pcal6416aError::Locked);
+            }
+        }
+
+        let (mask, values) = interlock::output_bits::<C>(address, data);
+        if mask != 0 {
+            if self.interlocks.missing(mask) != 0 {
+                let mut outputs = [0u8; LARGEST_REG_SIZE_BYTES];
+                device_driver::RegisterInterface::read_register(
+                    self,
+                    bank_register::<C>(Bank::Output),
+                    bank_bits::<C>(),
+                    &mut outputs[..C::PORTS],
+                )?;
+            }
+
+            let (first, allowed) = self.interlocks.check(mask, values);
+            if let Some(first) = first {
+                let mut broken = [0u8; LARGEST_REG_SIZE_BYTES];
+                broken[..data.len()].copy_from_slice(data);
+                interlock::set_output_bits::<C>(address, &mut broken[..data.len()], first);
+                self.transfer_write(address, &broken[..data.len()])?;
+                self.interlocks.commit(mask, first);
+            }
+            if !allowed {
+                return Err(Pcal6416aError::Interlock);
+            }
+        }
+
+        self.transfer_write(address, data)?;
+        self.interlocks.commit(mask, values);
+        Ok(())
     }
 
     fn read_register(
@@ -130,9 +501,16 @@ impl<I2c: embedded_hal::i2c::I2c> device_driver::RegisterInterface for Pcal6416a
         _size_bits: u32,
         data: &mut [u8],
     ) -> Result<(), Self::Error> {
-        self.i2cbus
-            .write_read(self.addr_pin.address(), &[address], data)
-            .map_err(Pcal6416aError::I2c)
+        if !self.supports(address) {
+            return Err(Pcal6416aError::Unsupported);
+        }
+
+        let result = self.i2cbus.write_read(self.i2c_address(), &[address], data);
+        self.track(result)?;
+
+        let (mask, values) = interlock::output_bits::<C>(address, data);
+        self.interlocks.observe(mask, values);
+        Ok(())
     }
 }
 
@@ -142,8 +520,33 @@ impl<I2c: embedded_hal::i2c::I2c> device_driver::RegisterInterface for Pcal6416a
 pub enum Port {
     /// Port 0 (pins 0-7)
     Port0,
-    /// Port 1 (pins 0-7)
+    /// Port 1 (pins 0-7), not present on the PCAL6408A
     Port1,
+    /// Port 2 (pins 0-7), PCAL6524 only
+    Port2,
+}
+
+impl Port {
+    /// Get the port index (0-2)
+    #[must_use]
+    pub const fn index(self) -> u8 {
+        match self {
+            Self::Port0 => 0,
+            Self::Port1 => 1,
+            Self::Port2 => 2,
+        }
+    }
+
+    /// Get the port with the given index, if any
+    #[must_use]
+    pub const fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(Self::Port0),
+            1 => Some(Self::Port1),
+            2 => Some(Self::Port2),
+            _ => None,
+        }
+    }
 }
 
 /// Pin number within a port (0-7) for the PCAL6416A device
@@ -189,6 +592,28 @@ impl Pin {
     pub const fn number(&self) -> u8 {
         self.bit()
     }
+
+    /// Get the bit mask of this pin within its port register
+    #[must_use]
+    pub const fn mask(self) -> u8 {
+        1 << self.bit()
+    }
+
+    /// Get the pin with the given bit position, if any
+    #[must_use]
+    pub const fn from_bit(bit: u8) -> Option<Self> {
+        match bit {
+            0 => Some(Self::Pin0),
+            1 => Some(Self::Pin1),
+            2 => Some(Self::Pin2),
+            3 => Some(Self::Pin3),
+            4 => Some(Self::Pin4),
+            5 => Some(Self::Pin5),
+            6 => Some(Self::Pin6),
+            7 => Some(Self::Pin7),
+            _ => None,
+        }
+    }
 }
 
 /// Individual pin instance that provides GPIO operations for a single pin
@@ -199,19 +624,22 @@ impl Pin {
 ///
 /// Note: This uses a shared mutex to provide safe concurrent access to the device.
 /// All pin operations acquire the mutex lock before performing I2C operations.
-pub struct IoPin<'a, I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::RawMutex> {
+pub struct IoPin<
+    'a,
+    I2c: embedded_hal_async::i2c::I2c,
+    M: embassy_sync::blocking_mutex::raw::RawMutex,
+    C: Chip = Pcal6416a,
+> {
     port: Port,
     pin: Pin,
-    device: &'a embassy_sync::mutex::Mutex<M, Device<Pcal6416aDevice<I2c>>>,
+    shared: &'a SharedDevice<I2c, M, C>,
 }
 
-impl<'a, I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::RawMutex> IoPin<'a, I2c, M> {
-    const fn new(
-        port: Port,
-        pin: Pin,
-        device: &'a embassy_sync::mutex::Mutex<M, Device<Pcal6416aDevice<I2c>>>,
-    ) -> Self {
-        Self { port, pin, device }
+impl<'a, I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::RawMutex, C: Chip>
+    IoPin<'a, I2c, M, C>
+{
+    const fn new(port: Port, pin: Pin, shared: &'a SharedDevice<I2c, M, C>) -> Self {
+        Self { port, pin, shared }
     }
 
     /// Get the pin number within the port (0-7)
@@ -231,15 +659,21 @@ impl<'a, I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw
     pub const fn port(&self) -> Port {
         self.port
     }
+
+    /// Get the index of this pin on its device (`port * 8 + pin`)
+    #[must_use]
+    pub const fn index(&self) -> u8 {
+        self.port.index() * 8 + self.pin.bit()
+    }
 }
 
-impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::RawMutex> IoPin<'_, I2c, M> {
+impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::RawMutex, C: Chip> IoPin<'_, I2c, M, C> {
     /// Read the state of this input pin (async version)
     /// # Errors
     ///
     /// Will return `Err` if underlying I2C bus operation fails
     pub async fn is_high_async(&self) -> Result<bool, Pcal6416aError<I2c::Error>> {
-        self.device.lock().await.is_pin_high_async(self.port, self.pin).await
+        Ok(self.shared.sample_port(self.port).await? & self.pin.mask() != 0)
     }
 
     /// Read the state of this input pin (async version)
@@ -255,7 +689,12 @@ impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::Ra
     ///
     /// Will return `Err` if underlying I2C bus operation fails
     pub async fn set_high_async(&self) -> Result<(), Pcal6416aError<I2c::Error>> {
-        self.device.lock().await.set_pin_high_async(self.port, self.pin).await
+        self.shared
+            .device
+            .lock()
+            .await
+            .set_pin_high_async(self.port, self.pin)
+            .await
     }
 
     /// Set this output pin to low state (async version)
@@ -263,7 +702,12 @@ impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::Ra
     ///
     /// Will return `Err` if underlying I2C bus operation fails
     pub async fn set_low_async(&self) -> Result<(), Pcal6416aError<I2c::Error>> {
-        self.device.lock().await.set_pin_low_async(self.port, self.pin).await
+        self.shared
+            .device
+            .lock()
+            .await
+            .set_pin_low_async(self.port, self.pin)
+            .await
     }
 
     /// Toggle this output pin state (async version)
@@ -271,7 +715,12 @@ impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::Ra
     ///
     /// Will return `Err` if underlying I2C bus operation fails
     pub async fn toggle_async(&self) -> Result<(), Pcal6416aError<I2c::Error>> {
-        self.device.lock().await.toggle_pin_async(self.port, self.pin).await
+        self.shared
+            .device
+            .lock()
+            .await
+            .toggle_pin_async(self.port, self.pin)
+            .await
     }
 
     /// Read the current state of this output pin (async version)
@@ -279,7 +728,8 @@ impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::Ra
     ///
     /// Will return `Err` if underlying I2C bus operation fails
     pub async fn is_set_high_async(&self) -> Result<bool, Pcal6416aError<I2c::Error>> {
-        self.device
+        self.shared
+            .device
             .lock()
             .await
             .is_pin_set_high_async(self.port, self.pin)
@@ -293,17 +743,45 @@ impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::Ra
     pub async fn is_set_low_async(&self) -> Result<bool, Pcal6416aError<I2c::Error>> {
         Ok(!self.is_set_high_async().await?)
     }
+
+    /// Configure this pin as an output, driving the level held in its output register (async version)
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn set_as_output_async(&self) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let mask = self.pin.mask();
+        self.shared
+            .device
+            .lock()
+            .await
+            .modify_port_async(Bank::Configuration, self.port, |config| config & !mask)
+            .await
+    }
+
+    /// Configure this pin as an input (async version)
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn set_as_input_async(&self) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let mask = self.pin.mask();
+        self.shared
+            .device
+            .lock()
+            .await
+            .modify_port_async(Bank::Configuration, self.port, |config| config | mask)
+            .await
+    }
 }
 
 // Implement embedded-hal digital traits for IoPin
-impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::RawMutex> embedded_hal::digital::ErrorType
-    for IoPin<'_, I2c, M>
+impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::RawMutex, C: Chip>
+    embedded_hal::digital::ErrorType for IoPin<'_, I2c, M, C>
 {
     type Error = Pcal6416aError<I2c::Error>;
 }
 
-impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::RawMutex>
-    embedded_hal_async::digital::InputPin for IoPin<'_, I2c, M>
+impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::RawMutex, C: Chip>
+    embedded_hal_async::digital::InputPin for IoPin<'_, I2c, M, C>
 {
     async fn is_high(&mut self) -> Result<bool, Self::Error> {
         IoPin::is_high_async(self).await
@@ -314,8 +792,8 @@ impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::Ra
     }
 }
 
-impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::RawMutex>
-    embedded_hal_async::digital::OutputPin for IoPin<'_, I2c, M>
+impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::RawMutex, C: Chip>
+    embedded_hal_async::digital::OutputPin for IoPin<'_, I2c, M, C>
 {
     async fn set_low(&mut self) -> Result<(), Self::Error> {
         IoPin::set_low_async(self).await
@@ -326,8 +804,8 @@ impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::Ra
     }
 }
 
-impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::RawMutex>
-    embedded_hal_async::digital::StatefulOutputPin for IoPin<'_, I2c, M>
+impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::RawMutex, C: Chip>
+    embedded_hal_async::digital::StatefulOutputPin for IoPin<'_, I2c, M, C>
 {
     async fn is_set_high(&mut self) -> Result<bool, Self::Error> {
         IoPin::is_set_high_async(self).await
@@ -342,28 +820,118 @@ impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::Ra
     }
 }
 
-impl<I2c: embedded_hal::i2c::I2c> Device<Pcal6416aDevice<I2c>> {
+/// Register address of `bank` for `port`, if the chip has that port
+fn port_register<C: Chip, E>(bank: Bank, port: Port) -> Result<u8, Pcal6416aError<E>> {
+    if usize::from(port.index()) < C::PORTS {
+        Ok(C::register(bank, port))
+    } else {
+        Err(Pcal6416aError::Unsupported)
+    }
+}
+
+/// Command byte addressing all ports of `bank` in a single transaction
+fn bank_register<C: Chip>(bank: Bank) -> u8 {
+    C::register(bank, Port::Port0) | C::AUTO_INCREMENT
+}
+
+/// Output Port Configuration value with the open-drain bit of `port` set or cleared
+const fn open_drain_bits(value: u8, port: Port, open_drain: bool) -> u8 {
+    let bit = 1 << port.index();
+    if open_drain { value | bit } else { value & !bit }
+}
+
+/// Size of a whole bank in bits
+#[allow(clippy::cast_possible_truncation)] // at most 24 pins
+const fn bank_bits<C: Chip>() -> u32 {
+    C::PINS as u32
+}
+
+impl<I2c: embedded_hal::i2c::I2c, C: Chip> Device<Pcal6416aDevice<I2c, C>> {
+    /// Read the register of `bank` for `port`
+    /// # Errors
+    ///
+    /// Will return `Err` if `port` does not exist on the chip or underlying I2C bus operation fails
+    pub fn read_port(&mut self, bank: Bank, port: Port) -> Result<u8, Pcal6416aError<I2c::Error>> {
+        let address = port_register::<C, I2c::Error>(bank, port)?;
+        let mut data = [0u8; 1];
+        device_driver::RegisterInterface::read_register(&mut self.interface, address, 8, &mut data)?;
+        Ok(data[0])
+    }
+
+    /// Write the register of `bank` for `port`
+    /// # Errors
+    ///
+    /// Will return `Err` if `port` does not exist on the chip or underlying I2C bus operation fails
+    pub fn write_port(&mut self, bank: Bank, port: Port, value: u8) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let address = port_register::<C, I2c::Error>(bank, port)?;
+        device_driver::RegisterInterface::write_register(&mut self.interface, address, 8, &[value])
+    }
+
+    /// Read-modify-write the register of `bank` for `port`
+    /// # Errors
+    ///
+    /// Will return `Err` if `port` does not exist on the chip or underlying I2C bus operation fails
+    pub fn modify_port(
+        &mut self,
+        bank: Bank,
+        port: Port,
+        f: impl FnOnce(u8) -> u8,
+    ) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let value = self.read_port(bank, port)?;
+        self.write_port(bank, port, f(value))
+    }
+
+    /// Select open-drain (`true`) or push-pull (`false`) outputs for all pins of `port`
+    /// # Errors
+    ///
+    /// Will return `Err` if `port` does not exist on the chip, the part lacks agile I/O or
+    /// underlying I2C bus operation fails
+    pub fn set_open_drain(&mut self, port: Port, open_drain: bool) -> Result<(), Pcal6416aError<I2c::Error>> {
+        if usize::from(port.index()) >= C::PORTS {
+            return Err(Pcal6416aError::Unsupported);
+        }
+
+        let mut data = [0u8; 1];
+        device_driver::RegisterInterface::read_register(&mut self.interface, C::OUTPUT_CONFIG, 8, &mut data)?;
+        let value = open_drain_bits(data[0], port, open_drain);
+        device_driver::RegisterInterface::write_register(&mut self.interface, C::OUTPUT_CONFIG, 8, &[value])
+    }
+
+    /// Read every port of `bank` in a single transaction, port 0 in the least significant byte
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub fn read_bank(&mut self, bank: Bank) -> Result<u32, Pcal6416aError<I2c::Error>> {
+        let mut data = [0u8; 4];
+        device_driver::RegisterInterface::read_register(
+            &mut self.interface,
+            bank_register::<C>(bank),
+            bank_bits::<C>(),
+            &mut data[..C::PORTS],
+        )?;
+        Ok(u32::from_le_bytes(data))
+    }
+
+    /// Write every port of `bank` in a single transaction, port 0 in the least significant byte
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub fn write_bank(&mut self, bank: Bank, value: u32) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let data = value.to_le_bytes();
+        device_driver::RegisterInterface::write_register(
+            &mut self.interface,
+            bank_register::<C>(bank),
+            bank_bits::<C>(),
+            &data[..C::PORTS],
+        )
+    }
+
     /// Read the state of an input pin
     /// # Errors
     ///
     /// Will return `Err` if underlying I2C bus operation fails
     pub fn is_pin_high(&mut self, port: Port, pin: Pin) -> Result<bool, Pcal6416aError<I2c::Error>> {
-        let bit = pin.bit();
-
-        let value = match port {
-            Port::Port0 => {
-                let reg = self.input_port_0().read()?;
-                let reg: [u8; 1] = reg.into();
-                reg[0] & (1 << bit) != 0
-            }
-            Port::Port1 => {
-                let reg = self.input_port_1().read()?;
-                let reg: [u8; 1] = reg.into();
-                reg[0] & (1 << bit) != 0
-            }
-        };
-
-        Ok(value)
+        Ok(self.read_port(Bank::Input, port)? & pin.mask() != 0)
     }
 
     /// Read the state of an input pin
@@ -379,32 +947,7 @@ impl<I2c: embedded_hal::i2c::I2c> Device<Pcal6416aDevice<I2c>> {
     ///
     /// Will return `Err` if underlying I2C bus operation fails
     pub fn set_pin_high(&mut self, port: Port, pin: Pin) -> Result<(), Pcal6416aError<I2c::Error>> {
-        let bit = pin.bit();
-
-        match port {
-            Port::Port0 => self.output_port_0().modify(|r| match bit {
-                0 => r.set_o_0_0(true),
-                1 => r.set_o_0_1(true),
-                2 => r.set_o_0_2(true),
-                3 => r.set_o_0_3(true),
-                4 => r.set_o_0_4(true),
-                5 => r.set_o_0_5(true),
-                6 => r.set_o_0_6(true),
-                7 => r.set_o_0_7(true),
-                _ => unreachable!(),
-            }),
-            Port::Port1 => self.output_port_1().modify(|r| match bit {
-                0 => r.set_o_1_0(true),
-                1 => r.set_o_1_1(true),
-                2 => r.set_o_1_2(true),
-                3 => r.set_o_1_3(true),
-                4 => r.set_o_1_4(true),
-                5 => r.set_o_1_5(true),
-                6 => r.set_o_1_6(true),
-                7 => r.set_o_1_7(true),
-                _ => unreachable!(),
-            }),
-        }
+        self.modify_port(Bank::Output, port, |r| r | pin.mask())
     }
 
     /// Set an output pin to low state
@@ -412,32 +955,7 @@ impl<I2c: embedded_hal::i2c::I2c> Device<Pcal6416aDevice<I2c>> {
     ///
     /// Will return `Err` if underlying I2C bus operation fails
     pub fn set_pin_low(&mut self, port: Port, pin: Pin) -> Result<(), Pcal6416aError<I2c::Error>> {
-        let bit = pin.bit();
-
-        match port {
-            Port::Port0 => self.output_port_0().modify(|r| match bit {
-                0 => r.set_o_0_0(false),
-                1 => r.set_o_0_1(false),
-                2 => r.set_o_0_2(false),
-                3 => r.set_o_0_3(false),
-                4 => r.set_o_0_4(false),
-                5 => r.set_o_0_5(false),
-                6 => r.set_o_0_6(false),
-                7 => r.set_o_0_7(false),
-                _ => unreachable!(),
-            }),
-            Port::Port1 => self.output_port_1().modify(|r| match bit {
-                0 => r.set_o_1_0(false),
-                1 => r.set_o_1_1(false),
-                2 => r.set_o_1_2(false),
-                3 => r.set_o_1_3(false),
-                4 => r.set_o_1_4(false),
-                5 => r.set_o_1_5(false),
-                6 => r.set_o_1_6(false),
-                7 => r.set_o_1_7(false),
-                _ => unreachable!(),
-            }),
-        }
+        self.modify_port(Bank::Output, port, |r| r & !pin.mask())
     }
 
     /// Toggle an output pin state
@@ -445,32 +963,7 @@ impl<I2c: embedded_hal::i2c::I2c> Device<Pcal6416aDevice<I2c>> {
     ///
     /// Will return `Err` if underlying I2C bus operation fails
     pub fn toggle_pin(&mut self, port: Port, pin: Pin) -> Result<(), Pcal6416aError<I2c::Error>> {
-        let bit = pin.bit();
-
-        match port {
-            Port::Port0 => self.output_port_0().modify(|r| match bit {
-                0 => r.set_o_0_0(!r.o_0_0()),
-                1 => r.set_o_0_1(!r.o_0_1()),
-                2 => r.set_o_0_2(!r.o_0_2()),
-                3 => r.set_o_0_3(!r.o_0_3()),
-                4 => r.set_o_0_4(!r.o_0_4()),
-                5 => r.set_o_0_5(!r.o_0_5()),
-                6 => r.set_o_0_6(!r.o_0_6()),
-                7 => r.set_o_0_7(!r.o_0_7()),
-                _ => unreachable!(),
-            }),
-            Port::Port1 => self.output_port_1().modify(|r| match bit {
-                0 => r.set_o_1_0(!r.o_1_0()),
-                1 => r.set_o_1_1(!r.o_1_1()),
-                2 => r.set_o_1_2(!r.o_1_2()),
-                3 => r.set_o_1_3(!r.o_1_3()),
-                4 => r.set_o_1_4(!r.o_1_4()),
-                5 => r.set_o_1_5(!r.o_1_5()),
-                6 => r.set_o_1_6(!r.o_1_6()),
-                7 => r.set_o_1_7(!r.o_1_7()),
-                _ => unreachable!(),
-            }),
-        }
+        self.modify_port(Bank::Output, port, |r| r ^ pin.mask())
     }
 
     /// Read the current state of an output pin
@@ -478,22 +971,7 @@ impl<I2c: embedded_hal::i2c::I2c> Device<Pcal6416aDevice<I2c>> {
     ///
     /// Will return `Err` if underlying I2C bus operation fails
     pub fn is_pin_set_high(&mut self, port: Port, pin: Pin) -> Result<bool, Pcal6416aError<I2c::Error>> {
-        let bit = pin.bit();
-
-        let value = match port {
-            Port::Port0 => {
-                let reg = self.output_port_0().read()?;
-                let reg: [u8; 1] = reg.into();
-                reg[0] & (1 << bit) != 0
-            }
-            Port::Port1 => {
-                let reg = self.output_port_1().read()?;
-                let reg: [u8; 1] = reg.into();
-                reg[0] & (1 << bit) != 0
-            }
-        };
-
-        Ok(value)
+        Ok(self.read_port(Bank::Output, port)? & pin.mask() != 0)
     }
 
     /// Read the current state of an output pin
@@ -505,28 +983,104 @@ impl<I2c: embedded_hal::i2c::I2c> Device<Pcal6416aDevice<I2c>> {
     }
 }
 
-impl<I2c: embedded_hal_async::i2c::I2c> Device<Pcal6416aDevice<I2c>> {
+impl<I2c: embedded_hal_async::i2c::I2c, C: Chip> Device<Pcal6416aDevice<I2c, C>> {
+    /// Read the register of `bank` for `port` (async version)
+    /// # Errors
+    ///
+    /// Will return `Err` if `port` does not exist on the chip or underlying I2C bus operation fails
+    pub async fn read_port_async(&mut self, bank: Bank, port: Port) -> Result<u8, Pcal6416aError<I2c::Error>> {
+        let address = port_register::<C, I2c::Error>(bank, port)?;
+        let mut data = [0u8; 1];
+        device_driver::AsyncRegisterInterface::read_register(&mut self.interface, address, 8, &mut data).await?;
+        Ok(data[0])
+    }
+
+    /// Write the register of `bank` for `port` (async version)
+    /// # Errors
+    ///
+    /// Will return `Err` if `port` does not exist on the chip or underlying I2C bus operation fails
+    pub async fn write_port_async(
+        &mut self,
+        bank: Bank,
+        port: Port,
+        value: u8,
+    ) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let address = port_register::<C, I2c::Error>(bank, port)?;
+        device_driver::AsyncRegisterInterface::write_register(&mut self.interface, address, 8, &[value]).await
+    }
+
+    /// Read-modify-write the register of `bank` for `port` (async version)
+    /// # Errors
+    ///
+    /// Will return `Err` if `port` does not exist on the chip or underlying I2C bus operation fails
+    pub async fn modify_port_async(
+        &mut self,
+        bank: Bank,
+        port: Port,
+        f: impl FnOnce(u8) -> u8,
+    ) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let value = self.read_port_async(bank, port).await?;
+        self.write_port_async(bank, port, f(value)).await
+    }
+
+    /// Select open-drain (`true`) or push-pull (`false`) outputs for all pins of `port` (async version)
+    /// # Errors
+    ///
+    /// Will return `Err` if `port` does not exist on the chip, the part lacks agile I/O or
+    /// underlying I2C bus operation fails
+    pub async fn set_open_drain_async(
+        &mut self,
+        port: Port,
+        open_drain: bool,
+    ) -> Result<(), Pcal6416aError<I2c::Error>> {
+        if usize::from(port.index()) >= C::PORTS {
+            return Err(Pcal6416aError::Unsupported);
+        }
+
+        let mut data = [0u8; 1];
+        device_driver::AsyncRegisterInterface::read_register(&mut self.interface, C::OUTPUT_CONFIG, 8, &mut data)
+            .await?;
+        let value = open_drain_bits(data[0], port, open_drain);
+        device_driver::AsyncRegisterInterface::write_register(&mut self.interface, C::OUTPUT_CONFIG, 8, &[value]).await
+    }
+
+    /// Read every port of `bank` in a single transaction, port 0 in the least significant byte (async version)
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn read_bank_async(&mut self, bank: Bank) -> Result<u32, Pcal6416aError<I2c::Error>> {
+        let mut data = [0u8; 4];
+        device_driver::AsyncRegisterInterface::read_register(
+            &mut self.interface,
+            bank_register::<C>(bank),
+            bank_bits::<C>(),
+            &mut data[..C::PORTS],
+        )
+        .await?;
+        Ok(u32::from_le_bytes(data))
+    }
+
+    /// Write every port of `bank` in a single transaction, port 0 in the least significant byte (async version)
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn write_bank_async(&mut self, bank: Bank, value: u32) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let data = value.to_le_bytes();
+        device_driver::AsyncRegisterInterface::write_register(
+            &mut self.interface,
+            bank_register::<C>(bank),
+            bank_bits::<C>(),
+            &data[..C::PORTS],
+        )
+        .await
+    }
+
     /// Read the state of an input pin (async version)
     /// # Errors
     ///
     /// Will return `Err` if underlying I2C bus operation fails
     pub async fn is_pin_high_async(&mut self, port: Port, pin: Pin) -> Result<bool, Pcal6416aError<I2c::Error>> {
-        let bit = pin.bit();
-
-        let value = match port {
-            Port::Port0 => {
-                let reg = self.input_port_0().read_async().await?;
-                let reg: [u8; 1] = reg.into();
-                reg[0] & (1 << bit) != 0
-            }
-            Port::Port1 => {
-                let reg = self.input_port_1().read_async().await?;
-                let reg: [u8; 1] = reg.into();
-                reg[0] & (1 << bit) != 0
-            }
-        };
-
-        Ok(value)
+        Ok(self.read_port_async(Bank::Input, port).await? & pin.mask() != 0)
     }
 
     /// Read the state of an input pin (async version)
@@ -542,40 +1096,7 @@ impl<I2c: embedded_hal_async::i2c::I2c> Device<Pcal6416aDevice<I2c>> {
     ///
     /// Will return `Err` if underlying I2C bus operation fails
     pub async fn set_pin_high_async(&mut self, port: Port, pin: Pin) -> Result<(), Pcal6416aError<I2c::Error>> {
-        let bit = pin.bit();
-
-        match port {
-            Port::Port0 => {
-                self.output_port_0()
-                    .modify_async(|r| match bit {
-                        0 => r.set_o_0_0(true),
-                        1 => r.set_o_0_1(true),
-                        2 => r.set_o_0_2(true),
-                        3 => r.set_o_0_3(true),
-                        4 => r.set_o_0_4(true),
-                        5 => r.set_o_0_5(true),
-                        6 => r.set_o_0_6(true),
-                        7 => r.set_o_0_7(true),
-                        _ => unreachable!(),
-                    })
-                    .await
-            }
-            Port::Port1 => {
-                self.output_port_1()
-                    .modify_async(|r| match bit {
-                        0 => r.set_o_1_0(true),
-                        1 => r.set_o_1_1(true),
-                        2 => r.set_o_1_2(true),
-                        3 => r.set_o_1_3(true),
-                        4 => r.set_o_1_4(true),
-                        5 => r.set_o_1_5(true),
-                        6 => r.set_o_1_6(true),
-                        7 => r.set_o_1_7(true),
-                        _ => unreachable!(),
-                    })
-                    .await
-            }
-        }
+        self.modify_port_async(Bank::Output, port, |r| r | pin.mask()).await
     }
 
     /// Set an output pin to low state (async version)
@@ -583,40 +1104,7 @@ impl<I2c: embedded_hal_async::i2c::I2c> Device<Pcal6416aDevice<I2c>> {
     ///
     /// Will return `Err` if underlying I2C bus operation fails
     pub async fn set_pin_low_async(&mut self, port: Port, pin: Pin) -> Result<(), Pcal6416aError<I2c::Error>> {
-        let bit = pin.bit();
-
-        match port {
-            Port::Port0 => {
-                self.output_port_0()
-                    .modify_async(|r| match bit {
-                        0 => r.set_o_0_0(false),
-                        1 => r.set_o_0_1(false),
-                        2 => r.set_o_0_2(false),
-                        3 => r.set_o_0_3(false),
-                        4 => r.set_o_0_4(false),
-                        5 => r.set_o_0_5(false),
-                        6 => r.set_o_0_6(false),
-                        7 => r.set_o_0_7(false),
-                        _ => unreachable!(),
-                    })
-                    .await
-            }
-            Port::Port1 => {
-                self.output_port_1()
-                    .modify_async(|r| match bit {
-                        0 => r.set_o_1_0(false),
-                        1 => r.set_o_1_1(false),
-                        2 => r.set_o_1_2(false),
-                        3 => r.set_o_1_3(false),
-                        4 => r.set_o_1_4(false),
-                        5 => r.set_o_1_5(false),
-                        6 => r.set_o_1_6(false),
-                        7 => r.set_o_1_7(false),
-                        _ => unreachable!(),
-                    })
-                    .await
-            }
-        }
+        self.modify_port_async(Bank::Output, port, |r| r & !pin.mask()).await
     }
 
     /// Toggle an output pin state (async version)
@@ -624,40 +1112,7 @@ impl<I2c: embedded_hal_async::i2c::I2c> Device<Pcal6416aDevice<I2c>> {
     ///
     /// Will return `Err` if underlying I2C bus operation fails
     pub async fn toggle_pin_async(&mut self, port: Port, pin: Pin) -> Result<(), Pcal6416aError<I2c::Error>> {
-        let bit = pin.bit();
-
-        match port {
-            Port::Port0 => {
-                self.output_port_0()
-                    .modify_async(|r| match bit {
-                        0 => r.set_o_0_0(!r.o_0_0()),
-                        1 => r.set_o_0_1(!r.o_0_1()),
-                        2 => r.set_o_0_2(!r.o_0_2()),
-                        3 => r.set_o_0_3(!r.o_0_3()),
-                        4 => r.set_o_0_4(!r.o_0_4()),
-                        5 => r.set_o_0_5(!r.o_0_5()),
-                        6 => r.set_o_0_6(!r.o_0_6()),
-                        7 => r.set_o_0_7(!r.o_0_7()),
-                        _ => unreachable!(),
-                    })
-                    .await
-            }
-            Port::Port1 => {
-                self.output_port_1()
-                    .modify_async(|r| match bit {
-                        0 => r.set_o_1_0(!r.o_1_0()),
-                        1 => r.set_o_1_1(!r.o_1_1()),
-                        2 => r.set_o_1_2(!r.o_1_2()),
-                        3 => r.set_o_1_3(!r.o_1_3()),
-                        4 => r.set_o_1_4(!r.o_1_4()),
-                        5 => r.set_o_1_5(!r.o_1_5()),
-                        6 => r.set_o_1_6(!r.o_1_6()),
-                        7 => r.set_o_1_7(!r.o_1_7()),
-                        _ => unreachable!(),
-                    })
-                    .await
-            }
-        }
+        self.modify_port_async(Bank::Output, port, |r| r ^ pin.mask()).await
     }
 
     /// Read the current state of an output pin (async version)
@@ -665,22 +1120,7 @@ impl<I2c: embedded_hal_async::i2c::I2c> Device<Pcal6416aDevice<I2c>> {
     ///
     /// Will return `Err` if underlying I2C bus operation fails
     pub async fn is_pin_set_high_async(&mut self, port: Port, pin: Pin) -> Result<bool, Pcal6416aError<I2c::Error>> {
-        let bit = pin.bit();
-
-        let value = match port {
-            Port::Port0 => {
-                let reg = self.output_port_0().read_async().await?;
-                let reg: [u8; 1] = reg.into();
-                reg[0] & (1 << bit) != 0
-            }
-            Port::Port1 => {
-                let reg = self.output_port_1().read_async().await?;
-                let reg: [u8; 1] = reg.into();
-                reg[0] & (1 << bit) != 0
-            }
-        };
-
-        Ok(value)
+        Ok(self.read_port_async(Bank::Output, port).await? & pin.mask() != 0)
     }
 
     /// Read the current state of an output pin (async version)
@@ -692,27 +1132,48 @@ impl<I2c: embedded_hal_async::i2c::I2c> Device<Pcal6416aDevice<I2c>> {
     }
 }
 
-impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::RawMutex> SharedDevice<I2c, M> {
+impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::RawMutex, C: Chip>
+    SharedDevice<I2c, M, C>
+{
     /// Create a new `SharedDevice` from a [`Device`] instance.
     ///
     /// The device is wrapped in a mutex to enable safe shared access
     /// from multiple [`IoPin`] instances.
-    pub fn new(device: Device<Pcal6416aDevice<I2c>>) -> Self {
+    pub fn new(device: Device<Pcal6416aDevice<I2c, C>>) -> Self {
         Self {
             device: embassy_sync::mutex::Mutex::new(device),
+            events: embassy_sync::blocking_mutex::Mutex::new(RefCell::new(EventState::new())),
+            channel: EventChannel::new(),
         }
     }
 
+    /// Drive the outputs selected by `mask` to the matching bits of `values`, bit
+    /// `port * 8 + pin`, in a single write of the output registers
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn write_outputs(&self, mask: u32, values: u32) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let mut device = self.device.lock().await;
+        let current = device.read_bank_async(Bank::Output).await?;
+        device
+            .write_bank_async(Bank::Output, (current & !mask) | (values & mask))
+            .await
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::RawMutex>
+    SharedDevice<I2c, M, Pcal6416a>
+{
     /// Split the driver into an array of individual pin instances
     ///
-    /// This borrows the shared device mutably and returns an array of 16 `IoPin` instances,
+    /// This borrows the shared device and returns an array of 16 `IoPin` instances,
     /// one for each GPIO pin. The pins can be passed individually to different functions.
     /// Each pin uses the shared mutex to safely access the underlying device.
     ///
     /// # Example
     /// ```ignore
-    /// let device = Device::new(Pcal6416aDevice { addr_pin, i2cbus });
-    /// let mut shared = SharedDevice::new(device);
+    /// let device = Device::new(Pcal6416aDevice::new(addr_pin, i2cbus));
+    /// let shared = SharedDevice::new(device);
     /// let pins = shared.split();
     ///
     /// // Pass individual pins to different functions
@@ -728,24 +1189,78 @@ impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::Ra
     ///     println!("Pin {} number: {}", i, pin.number());
     /// }
     /// ```
-    pub fn split(&mut self) -> [IoPin<'_, I2c, M>; 16] {
+    pub fn split(&self) -> [IoPin<'_, I2c, M>; 16] {
         [
-            IoPin::new(Port::Port0, Pin::Pin0, &self.device),
-            IoPin::new(Port::Port0, Pin::Pin1, &self.device),
-            IoPin::new(Port::Port0, Pin::Pin2, &self.device),
-            IoPin::new(Port::Port0, Pin::Pin3, &self.device),
-            IoPin::new(Port::Port0, Pin::Pin4, &self.device),
-            IoPin::new(Port::Port0, Pin::Pin5, &self.device),
-            IoPin::new(Port::Port0, Pin::Pin6, &self.device),
-            IoPin::new(Port::Port0, Pin::Pin7, &self.device),
-            IoPin::new(Port::Port1, Pin::Pin0, &self.device),
-            IoPin::new(Port::Port1, Pin::Pin1, &self.device),
-            IoPin::new(Port::Port1, Pin::Pin2, &self.device),
-            IoPin::new(Port::Port1, Pin::Pin3, &self.device),
-            IoPin::new(Port::Port1, Pin::Pin4, &self.device),
-            IoPin::new(Port::Port1, Pin::Pin5, &self.device),
-            IoPin::new(Port::Port1, Pin::Pin6, &self.device),
-            IoPin::new(Port::Port1, Pin::Pin7, &self.device),
+            IoPin::new(Port::Port0, Pin::Pin0, self),
+            IoPin::new(Port::Port0, Pin::Pin1, self),
+            IoPin::new(Port::Port0, Pin::Pin2, self),
+            IoPin::new(Port::Port0, Pin::Pin3, self),
+            IoPin::new(Port::Port0, Pin::Pin4, self),
+            IoPin::new(Port::Port0, Pin::Pin5, self),
+            IoPin::new(Port::Port0, Pin::Pin6, self),
+            IoPin::new(Port::Port0, Pin::Pin7, self),
+            IoPin::new(Port::Port1, Pin::Pin0, self),
+            IoPin::new(Port::Port1, Pin::Pin1, self),
+            IoPin::new(Port::Port1, Pin::Pin2, self),
+            IoPin::new(Port::Port1, Pin::Pin3, self),
+            IoPin::new(Port::Port1, Pin::Pin4, self),
+            IoPin::new(Port::Port1, Pin::Pin5, self),
+            IoPin::new(Port::Port1, Pin::Pin6, self),
+            IoPin::new(Port::Port1, Pin::Pin7, self),
+        ]
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::RawMutex>
+    SharedDevice<I2c, M, Pcal6408a>
+{
+    /// Split the driver into an array of individual pin instances
+    ///
+    /// Returns the 8 `IoPin` instances of a PCAL6408A, see [`SharedDevice::split`].
+    pub fn split(&self) -> [IoPin<'_, I2c, M, Pcal6408a>; 8] {
+        [
+            IoPin::new(Port::Port0, Pin::Pin0, self),
+            IoPin::new(Port::Port0, Pin::Pin1, self),
+            IoPin::new(Port::Port0, Pin::Pin2, self),
+            IoPin::new(Port::Port0, Pin::Pin3, self),
+            IoPin::new(Port::Port0, Pin::Pin4, self),
+            IoPin::new(Port::Port0, Pin::Pin5, self),
+            IoPin::new(Port::Port0, Pin::Pin6, self),
+            IoPin::new(Port::Port0, Pin::Pin7, self),
+        ]
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: embassy_sync::blocking_mutex::raw::RawMutex> SharedDevice<I2c, M, Pcal6524> {
+    /// Split the driver into an array of individual pin instances
+    ///
+    /// Returns the 24 `IoPin` instances of a PCAL6524, see [`SharedDevice::split`].
+    pub fn split(&self) -> [IoPin<'_, I2c, M, Pcal6524>; 24] {
+        [
+            IoPin::new(Port::Port0, Pin::Pin0, self),
+            IoPin::new(Port::Port0, Pin::Pin1, self),
+            IoPin::new(Port::Port0, Pin::Pin2, self),
+            IoPin::new(Port::Port0, Pin::Pin3, self),
+            IoPin::new(Port::Port0, Pin::Pin4, self),
+            IoPin::new(Port::Port0, Pin::Pin5, self),
+            IoPin::new(Port::Port0, Pin::Pin6, self),
+            IoPin::new(Port::Port0, Pin::Pin7, self),
+            IoPin::new(Port::Port1, Pin::Pin0, self),
+            IoPin::new(Port::Port1, Pin::Pin1, self),
+            IoPin::new(Port::Port1, Pin::Pin2, self),
+            IoPin::new(Port::Port1, Pin::Pin3, self),
+            IoPin::new(Port::Port1, Pin::Pin4, self),
+            IoPin::new(Port::Port1, Pin::Pin5, self),
+            IoPin::new(Port::Port1, Pin::Pin6, self),
+            IoPin::new(Port::Port1, Pin::Pin7, self),
+            IoPin::new(Port::Port2, Pin::Pin0, self),
+            IoPin::new(Port::Port2, Pin::Pin1, self),
+            IoPin::new(Port::Port2, Pin::Pin2, self),
+            IoPin::new(Port::Port2, Pin::Pin3, self),
+            IoPin::new(Port::Port2, Pin::Pin4, self),
+            IoPin::new(Port::Port2, Pin::Pin5, self),
+            IoPin::new(Port::Port2, Pin::Pin6, self),
+            IoPin::new(Port::Port2, Pin::Pin7, self),
         ]
     }
 }
@@ -760,10 +1275,7 @@ mod tests {
     async fn read_input_port_0_async() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x00], vec![0b01110111])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let input_port_0 = dev.input_port_0().read_async().await.unwrap();
         assert_eq!(input_port_0.i_0_7(), false);
         assert_eq!(input_port_0.i_0_6(), true);
@@ -780,10 +1292,7 @@ mod tests {
     fn read_input_port_0() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x00], vec![0b01110111])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let input_port_0 = dev.input_port_0().read().unwrap();
         assert_eq!(input_port_0.i_0_7(), false);
         assert_eq!(input_port_0.i_0_6(), true);
@@ -800,10 +1309,7 @@ mod tests {
     async fn read_input_port_1_async() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x01], vec![0b01010101])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let input_port_1 = dev.input_port_1().read_async().await.unwrap();
         assert_eq!(input_port_1.i_1_7(), false);
         assert_eq!(input_port_1.i_1_6(), true);
@@ -820,10 +1326,7 @@ mod tests {
     fn read_input_port_1() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x01], vec![0b01010101])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let input_port_1 = dev.input_port_1().read().unwrap();
         assert_eq!(input_port_1.i_1_7(), false);
         assert_eq!(input_port_1.i_1_6(), true);
@@ -840,10 +1343,7 @@ mod tests {
     async fn read_output_port_0_async() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x02], vec![0b01000011])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let output_port_0 = dev.output_port_0().read_async().await.unwrap();
         assert_eq!(output_port_0.o_0_7(), false);
         assert_eq!(output_port_0.o_0_6(), true);
@@ -860,10 +1360,7 @@ mod tests {
     fn read_output_port_0() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x02], vec![0b01000011])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let output_port_0 = dev.output_port_0().read().unwrap();
         assert_eq!(output_port_0.o_0_7(), false);
         assert_eq!(output_port_0.o_0_6(), true);
@@ -880,10 +1377,7 @@ mod tests {
     async fn write_output_port_0_async() {
         let expectations = vec![Transaction::write(IOEXP_ADDR_LOW, vec![0x02, 0b11110101])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.output_port_0()
             .write_async(|c| {
                 c.set_o_0_7(true);
@@ -904,10 +1398,7 @@ mod tests {
     fn write_output_port_0() {
         let expectations = vec![Transaction::write(IOEXP_ADDR_LOW, vec![0x02, 0b11110101])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.output_port_0()
             .write(|c| {
                 c.set_o_0_7(true);
@@ -927,10 +1418,7 @@ mod tests {
     async fn read_output_port_1_async() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x03], vec![0b01010010])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let output_port_1 = dev.output_port_1().read_async().await.unwrap();
         assert_eq!(output_port_1.o_1_7(), false);
         assert_eq!(output_port_1.o_1_6(), true);
@@ -947,10 +1435,7 @@ mod tests {
     fn read_output_port_1() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x03], vec![0b01010010])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let output_port_1 = dev.output_port_1().read().unwrap();
         assert_eq!(output_port_1.o_1_7(), false);
         assert_eq!(output_port_1.o_1_6(), true);
@@ -967,10 +1452,7 @@ mod tests {
     async fn write_output_port_1_async() {
         let expectations = vec![Transaction::write(IOEXP_ADDR_LOW, vec![0x03, 0b11010101])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.output_port_1()
             .write_async(|c| {
                 c.set_o_1_7(true);
@@ -991,10 +1473,7 @@ mod tests {
     fn write_output_port_1() {
         let expectations = vec![Transaction::write(IOEXP_ADDR_LOW, vec![0x03, 0b11010101])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.output_port_1()
             .write(|c| {
                 c.set_o_1_7(true);
@@ -1014,10 +1493,7 @@ mod tests {
     async fn read_config_port_0_async() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x06], vec![0b01010111])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let config_port_0 = dev.config_port_0().read_async().await.unwrap();
         assert_eq!(config_port_0.c_0_7(), false);
         assert_eq!(config_port_0.c_0_6(), true);
@@ -1034,10 +1510,7 @@ mod tests {
     fn read_config_port_0() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x06], vec![0b01010111])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let config_port_0 = dev.config_port_0().read().unwrap();
         assert_eq!(config_port_0.c_0_7(), false);
         assert_eq!(config_port_0.c_0_6(), true);
@@ -1054,10 +1527,7 @@ mod tests {
     async fn write_config_port_0_async() {
         let expectations = vec![Transaction::write(IOEXP_ADDR_LOW, vec![0x06, 0b01010101])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.config_port_0()
             .write_async(|c| {
                 c.set_c_0_7(false);
@@ -1078,10 +1548,7 @@ mod tests {
     fn write_config_port_0() {
         let expectations = vec![Transaction::write(IOEXP_ADDR_LOW, vec![0x06, 0b01010101])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.config_port_0()
             .write(|c| {
                 c.set_c_0_7(false);
@@ -1101,10 +1568,7 @@ mod tests {
     async fn read_config_port_1_async() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x07], vec![0b01110111])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let config_port_1 = dev.config_port_1().read_async().await.unwrap();
         assert_eq!(config_port_1.c_1_7(), false);
         assert_eq!(config_port_1.c_1_6(), true);
@@ -1121,10 +1585,7 @@ mod tests {
     fn read_config_port_1() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x07], vec![0b01110111])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let config_port_1 = dev.config_port_1().read().unwrap();
         assert_eq!(config_port_1.c_1_7(), false);
         assert_eq!(config_port_1.c_1_6(), true);
@@ -1141,10 +1602,7 @@ mod tests {
     async fn write_config_port_1_async() {
         let expectations = vec![Transaction::write(IOEXP_ADDR_LOW, vec![0x07, 0b11110101])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.config_port_1()
             .write_async(|c| {
                 c.set_c_1_7(true);
@@ -1165,10 +1623,7 @@ mod tests {
     fn write_config_port_1() {
         let expectations = vec![Transaction::write(IOEXP_ADDR_LOW, vec![0x07, 0b11110101])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.config_port_1()
             .write(|c| {
                 c.set_c_1_7(true);
@@ -1188,10 +1643,7 @@ mod tests {
     async fn read_pull_up_down_enable_port_0_async() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x46], vec![0b00111010])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let pull_up_down_enable_port_0 = dev.pull_up_down_enable_port_0().read_async().await.unwrap();
         assert_eq!(pull_up_down_enable_port_0.pe_0_7(), false);
         assert_eq!(pull_up_down_enable_port_0.pe_0_6(), false);
@@ -1208,10 +1660,7 @@ mod tests {
     fn read_pull_up_down_enable_port_0() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x46], vec![0b00111010])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let pull_up_down_enable_port_0 = dev.pull_up_down_enable_port_0().read().unwrap();
         assert_eq!(pull_up_down_enable_port_0.pe_0_7(), false);
         assert_eq!(pull_up_down_enable_port_0.pe_0_6(), false);
@@ -1228,10 +1677,7 @@ mod tests {
     async fn write_pull_up_down_enable_port_0_async() {
         let expectations = vec![Transaction::write(IOEXP_ADDR_LOW, vec![0x46, 0b00111010])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.pull_up_down_enable_port_0()
             .write_async(|c| {
                 c.set_pe_0_7(false);
@@ -1252,10 +1698,7 @@ mod tests {
     fn write_pull_up_down_enable_port_0() {
         let expectations = vec![Transaction::write(IOEXP_ADDR_LOW, vec![0x46, 0b00111010])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.pull_up_down_enable_port_0()
             .write(|c| {
                 c.set_pe_0_7(false);
@@ -1275,10 +1718,7 @@ mod tests {
     async fn read_pull_up_down_enable_port_1_async() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x47], vec![0b11101100])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let pull_up_down_enable_port_1 = dev.pull_up_down_enable_port_1().read_async().await.unwrap();
         assert_eq!(pull_up_down_enable_port_1.pe_1_7(), true);
         assert_eq!(pull_up_down_enable_port_1.pe_1_6(), true);
@@ -1295,10 +1735,7 @@ mod tests {
     fn read_pull_up_down_enable_port_1() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x47], vec![0b11101100])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let pull_up_down_enable_port_1 = dev.pull_up_down_enable_port_1().read().unwrap();
         assert_eq!(pull_up_down_enable_port_1.pe_1_7(), true);
         assert_eq!(pull_up_down_enable_port_1.pe_1_6(), true);
@@ -1315,10 +1752,7 @@ mod tests {
     async fn write_pull_up_down_enable_port_1_async() {
         let expectations = vec![Transaction::write(IOEXP_ADDR_LOW, vec![0x47, 0b01011100])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.pull_up_down_enable_port_1()
             .write_async(|c| {
                 c.set_pe_1_7(false);
@@ -1339,10 +1773,7 @@ mod tests {
     fn write_pull_up_down_enable_port_1() {
         let expectations = vec![Transaction::write(IOEXP_ADDR_LOW, vec![0x47, 0b11101010])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.pull_up_down_enable_port_1()
             .write(|c| {
                 c.set_pe_1_7(true);
@@ -1362,10 +1793,7 @@ mod tests {
     async fn read_pull_up_down_select_port_0_async() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x48], vec![0b00111010])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let pull_up_down_select_port_0 = dev.pull_up_down_select_port_0().read_async().await.unwrap();
         assert_eq!(pull_up_down_select_port_0.pud_0_7(), false);
         assert_eq!(pull_up_down_select_port_0.pud_0_6(), false);
@@ -1382,10 +1810,7 @@ mod tests {
     fn read_pull_up_down_select_port_0() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x48], vec![0b00111010])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let pull_up_down_select_port_0 = dev.pull_up_down_select_port_0().read().unwrap();
         assert_eq!(pull_up_down_select_port_0.pud_0_7(), false);
         assert_eq!(pull_up_down_select_port_0.pud_0_6(), false);
@@ -1402,10 +1827,7 @@ mod tests {
     async fn write_pull_up_down_select_port_0_async() {
         let expectations = vec![Transaction::write(IOEXP_ADDR_LOW, vec![0x48, 0b01011001])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.pull_up_down_select_port_0()
             .write_async(|c| {
                 c.set_pud_0_7(false);
@@ -1426,10 +1848,7 @@ mod tests {
     fn write_pull_up_down_select_port_0() {
         let expectations = vec![Transaction::write(IOEXP_ADDR_LOW, vec![0x48, 0b11101010])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.pull_up_down_select_port_0()
             .write(|c| {
                 c.set_pud_0_7(true);
@@ -1449,10 +1868,7 @@ mod tests {
     async fn read_pull_up_down_select_port_1_async() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x49], vec![0b01100111])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let pull_up_down_select_port_1 = dev.pull_up_down_select_port_1().read_async().await.unwrap();
         assert_eq!(pull_up_down_select_port_1.pud_1_7(), false);
         assert_eq!(pull_up_down_select_port_1.pud_1_6(), true);
@@ -1469,10 +1885,7 @@ mod tests {
     fn read_pull_up_down_select_port_1() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x49], vec![0b01100111])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let pull_up_down_select_port_1 = dev.pull_up_down_select_port_1().read().unwrap();
         assert_eq!(pull_up_down_select_port_1.pud_1_7(), false);
         assert_eq!(pull_up_down_select_port_1.pud_1_6(), true);
@@ -1489,10 +1902,7 @@ mod tests {
     async fn write_pull_up_down_select_port_1_async() {
         let expectations = vec![Transaction::write(IOEXP_ADDR_LOW, vec![0x49, 0b00011011])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.pull_up_down_select_port_1()
             .write_async(|c| {
                 c.set_pud_1_7(false);
@@ -1513,10 +1923,7 @@ mod tests {
     fn write_pull_up_down_select_port_1() {
         let expectations = vec![Transaction::write(IOEXP_ADDR_LOW, vec![0x49, 0b00011011])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.pull_up_down_select_port_1()
             .write(|c| {
                 c.set_pud_1_7(false);
@@ -1536,10 +1943,7 @@ mod tests {
     async fn write_low_address() {
         let expectations = vec![Transaction::write(IOEXP_ADDR_LOW, vec![0x07, 0])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.config_port_1()
             .write_async(|c| {
                 c.set_c_1_7(false);
@@ -1560,10 +1964,7 @@ mod tests {
     async fn write_high_address() {
         let expectations = vec![Transaction::write(IOEXP_ADDR_HIGH, vec![0x07, 0x0])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::High,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::High, i2cbus));
         dev.config_port_1()
             .write_async(|c| {
                 c.set_c_1_7(false);
@@ -1584,10 +1985,7 @@ mod tests {
     async fn read_low_address() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x07], vec![0x0])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         let _ = dev.config_port_1().read_async().await.unwrap();
         dev.interface.i2cbus.done();
     }
@@ -1596,11 +1994,66 @@ mod tests {
     async fn read_high_address() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_HIGH, vec![0x07], vec![0x0])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::High,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::High, i2cbus));
+        let _ = dev.config_port_1().read_async().await.unwrap();
+        dev.interface.i2cbus.done();
+    }
+
+    #[tokio::test]
+    async fn variant_without_agile_io_async() {
+        let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x00], vec![0b0000_0001])];
+        let i2cbus = Mock::new(&expectations);
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus).with_variant(Variant::Pca6416a));
+        assert!(dev.input_port_0().read_async().await.unwrap().i_0_0());
+        assert_eq!(
+            dev.pull_up_down_enable_port_0().read_async().await.unwrap_err(),
+            Pcal6416aError::Unsupported
+        );
+        assert_eq!(
+            dev.pull_up_down_select_port_1()
+                .write_async(|c| c.set_pud_1_0(false))
+                .await
+                .unwrap_err(),
+            Pcal6416aError::Unsupported
+        );
+        dev.interface.i2cbus.done();
+    }
+
+    #[test]
+    fn variant_without_agile_io() {
+        let expectations = vec![Transaction::write(IOEXP_ADDR_LOW, vec![0x06, 0b1111_1110])];
+        let i2cbus = Mock::new(&expectations);
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus).with_variant(Variant::Tca6416));
+        dev.config_port_0().write(|c| c.set_c_0_0(false)).unwrap();
+        assert_eq!(
+            dev.pull_up_down_enable_port_1().read().unwrap_err(),
+            Pcal6416aError::Unsupported
+        );
+        dev.interface.i2cbus.done();
+    }
+
+    #[tokio::test]
+    async fn explicit_address_async() {
+        let expectations = vec![
+            Transaction::write_read(0x52, vec![0x07], vec![0x0]),
+            Transaction::write(0x52, vec![0x07, 0b1111_1110]),
+        ];
+        let i2cbus = Mock::new(&expectations);
+        let mut dev = Device::new(Pcal6416aDevice::new(Address::explicit(0x52).unwrap(), i2cbus));
         let _ = dev.config_port_1().read_async().await.unwrap();
+        dev.config_port_1().write_async(|c| c.set_c_1_0(false)).await.unwrap();
+        dev.interface.i2cbus.done();
+    }
+
+    #[test]
+    fn explicit_address() {
+        let expectations = vec![Transaction::write_read(0x7F, vec![0x00], vec![0b0000_0001])];
+        let i2cbus = Mock::new(&expectations);
+        let mut dev = Device::new(Pcal6416aDevice::new(Address::try_from(0x7F).unwrap(), i2cbus));
+        assert_eq!(dev.interface.address(), Address::Explicit(ExplicitAddress(0x7F)));
+        assert!(dev.is_pin_high(Port::Port0, Pin::Pin0).unwrap());
+        assert_eq!(Address::try_from(0x80), Err(InvalidAddress(0x80)));
+        assert_eq!(ExplicitAddress::new(0x80), None);
         dev.interface.i2cbus.done();
     }
 
@@ -1613,10 +2066,7 @@ mod tests {
             Transaction::write_read(IOEXP_ADDR_LOW, vec![0x01], vec![0b1000_0000]),
         ];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         assert!(dev.is_pin_high(Port::Port0, Pin::Pin0).unwrap());
         assert!(dev.is_pin_high(Port::Port1, Pin::Pin7).unwrap());
         dev.interface.i2cbus.done();
@@ -1631,10 +2081,7 @@ mod tests {
             Transaction::write_read(IOEXP_ADDR_LOW, vec![0x01], vec![0b0000_0000]),
         ];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         assert!(dev.is_pin_low(Port::Port0, Pin::Pin0).unwrap());
         assert!(dev.is_pin_low(Port::Port1, Pin::Pin7).unwrap());
         dev.interface.i2cbus.done();
@@ -1644,10 +2091,7 @@ mod tests {
     fn input_pin_port1() {
         let expectations = vec![Transaction::write_read(IOEXP_ADDR_LOW, vec![0x01], vec![0b1000_0000])];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         assert!(dev.is_pin_high(Port::Port1, Pin::Pin7).unwrap());
         dev.interface.i2cbus.done();
     }
@@ -1663,10 +2107,7 @@ mod tests {
             Transaction::write(IOEXP_ADDR_LOW, vec![0x03, 0b1000_0000]),
         ];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.set_pin_high(Port::Port0, Pin::Pin0).unwrap();
         dev.set_pin_high(Port::Port1, Pin::Pin7).unwrap();
         dev.interface.i2cbus.done();
@@ -1683,10 +2124,7 @@ mod tests {
             Transaction::write(IOEXP_ADDR_LOW, vec![0x03, 0b0111_1111]),
         ];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.set_pin_low(Port::Port0, Pin::Pin0).unwrap();
         dev.set_pin_low(Port::Port1, Pin::Pin7).unwrap();
         dev.interface.i2cbus.done();
@@ -1699,10 +2137,7 @@ mod tests {
             Transaction::write(IOEXP_ADDR_LOW, vec![0x03, 0b1111_1111]),
         ];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.set_pin_high(Port::Port1, Pin::Pin7).unwrap();
         dev.interface.i2cbus.done();
     }
@@ -1718,10 +2153,7 @@ mod tests {
             Transaction::write(IOEXP_ADDR_LOW, vec![0x03, 0b0000_0000]),
         ];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         dev.toggle_pin(Port::Port0, Pin::Pin0).unwrap();
         dev.toggle_pin(Port::Port1, Pin::Pin7).unwrap();
         dev.interface.i2cbus.done();
@@ -1736,10 +2168,7 @@ mod tests {
             Transaction::write_read(IOEXP_ADDR_LOW, vec![0x03], vec![0b1000_0000]),
         ];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         assert!(dev.is_pin_set_high(Port::Port0, Pin::Pin0).unwrap());
         assert!(dev.is_pin_set_high(Port::Port1, Pin::Pin7).unwrap());
         dev.interface.i2cbus.done();
@@ -1755,10 +2184,7 @@ mod tests {
             Transaction::write_read(IOEXP_ADDR_LOW, vec![0x00], vec![0b1111_1111]),
         ];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
         // Set multiple pins without borrowing conflicts
         dev.set_pin_high(Port::Port0, Pin::Pin0).unwrap();
         dev.set_pin_high(Port::Port0, Pin::Pin1).unwrap();
@@ -1766,6 +2192,66 @@ mod tests {
         dev.interface.i2cbus.done();
     }
 
+    #[test]
+    fn bank_read_write() {
+        let expectations = vec![
+            Transaction::write_read(IOEXP_ADDR_LOW, vec![0x00], vec![0b0000_0001, 0b1000_0000]),
+            Transaction::write(IOEXP_ADDR_LOW, vec![0x02, 0b1010_1010, 0b0101_0101]),
+        ];
+        let i2cbus = Mock::new(&expectations);
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
+        assert_eq!(dev.read_bank(Bank::Input).unwrap(), 0x8001);
+        dev.write_bank(Bank::Output, 0x55AA).unwrap();
+        dev.interface.i2cbus.done();
+    }
+
+    #[test]
+    fn pcal6408a_pins() {
+        let expectations = vec![
+            Transaction::write_read(IOEXP_ADDR_HIGH, vec![0x01], vec![0b0000_0000]),
+            Transaction::write(IOEXP_ADDR_HIGH, vec![0x01, 0b1000_0000]),
+            Transaction::write_read(IOEXP_ADDR_HIGH, vec![0x00], vec![0b0000_0100]),
+        ];
+        let i2cbus = Mock::new(&expectations);
+        let mut dev = Device::new(Pcal6416aDevice::<_, Pcal6408a>::new_for_chip(
+            AddrPinState::High,
+            i2cbus,
+        ));
+        dev.set_pin_high(Port::Port0, Pin::Pin7).unwrap();
+        assert!(dev.is_pin_high(Port::Port0, Pin::Pin2).unwrap());
+        assert_eq!(
+            dev.set_pin_high(Port::Port1, Pin::Pin0).unwrap_err(),
+            Pcal6416aError::Unsupported
+        );
+        dev.interface.i2cbus.done();
+    }
+
+    #[tokio::test]
+    async fn pcal6524_pins() {
+        let expectations = vec![
+            // Whole input bank with auto-increment
+            Transaction::write_read(0x22, vec![0x80], vec![0x01, 0x02, 0x04]),
+            Transaction::write_read(0x22, vec![0x06], vec![0b0000_0000]),
+            Transaction::write(0x22, vec![0x06, 0b0000_1000]),
+        ];
+        let i2cbus = Mock::new(&expectations);
+        let dev = Device::new(Pcal6416aDevice::<_, Pcal6524>::new_for_chip(AddrPinState::Low, i2cbus));
+        let dev: SharedDevice<_, embassy_sync::blocking_mutex::raw::NoopRawMutex, Pcal6524> = SharedDevice::new(dev);
+
+        assert_eq!(
+            dev.device.lock().await.read_bank_async(Bank::Input).await.unwrap(),
+            0x04_0201
+        );
+        {
+            let pins = dev.split();
+            assert_eq!(pins[19].port(), Port::Port2);
+            assert_eq!(pins[19].pin(), Pin::Pin3);
+            pins[19].set_high_async().await.unwrap();
+        }
+
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+
     #[tokio::test]
     async fn split_pins() {
         let expectations = vec![
@@ -1782,11 +2268,8 @@ mod tests {
             Transaction::write(IOEXP_ADDR_LOW, vec![0x02, 0b0000_0001]),
         ];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
-        let mut dev = SharedDevice::new(dev);
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
+        let dev = SharedDevice::new(dev);
 
         {
             let mut pins: [IoPin<
@@ -1810,11 +2293,8 @@ mod tests {
     async fn split_pin_numbers() {
         let expectations = vec![];
         let i2cbus = Mock::new(&expectations);
-        let dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
-        let mut dev = SharedDevice::new(dev);
+        let dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
+        let dev = SharedDevice::new(dev);
 
         {
             let pins: [IoPin<
@@ -1878,11 +2358,8 @@ mod tests {
             Transaction::write(IOEXP_ADDR_LOW, vec![0x03, 0b1000_0000]),
         ];
         let i2cbus = Mock::new(&expectations);
-        let dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
-        let mut dev = SharedDevice::new(dev);
+        let dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
+        let dev = SharedDevice::new(dev);
 
         {
             let pins: [IoPin<
@@ -1908,11 +2385,8 @@ mod tests {
             Transaction::write(IOEXP_ADDR_LOW, vec![0x03, 0b0000_0000]),
         ];
         let i2cbus = Mock::new(&expectations);
-        let dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
-        let mut dev = SharedDevice::new(dev);
+        let dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
+        let dev = SharedDevice::new(dev);
 
         {
             let pins: [IoPin<
@@ -1936,11 +2410,8 @@ mod tests {
             Transaction::write_read(IOEXP_ADDR_LOW, vec![0x01], vec![0b1000_0000]),
         ];
         let i2cbus = Mock::new(&expectations);
-        let dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
-        let mut dev = SharedDevice::new(dev);
+        let dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
+        let dev = SharedDevice::new(dev);
 
         {
             let pins: [IoPin<
@@ -1964,11 +2435,8 @@ mod tests {
             Transaction::write_read(IOEXP_ADDR_LOW, vec![0x01], vec![0b0000_0000]),
         ];
         let i2cbus = Mock::new(&expectations);
-        let dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
-        let mut dev = SharedDevice::new(dev);
+        let dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
+        let dev = SharedDevice::new(dev);
 
         {
             let pins: [IoPin<
@@ -1994,11 +2462,8 @@ mod tests {
             Transaction::write(IOEXP_ADDR_LOW, vec![0x03, 0b0000_0000]),
         ];
         let i2cbus = Mock::new(&expectations);
-        let dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
-        let mut dev = SharedDevice::new(dev);
+        let dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
+        let dev = SharedDevice::new(dev);
 
         {
             let pins: [IoPin<
@@ -2022,11 +2487,8 @@ mod tests {
             Transaction::write_read(IOEXP_ADDR_LOW, vec![0x03], vec![0b1000_0000]),
         ];
         let i2cbus = Mock::new(&expectations);
-        let dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
-        let mut dev = SharedDevice::new(dev);
+        let dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
+        let dev = SharedDevice::new(dev);
 
         {
             let pins: [IoPin<
@@ -2050,11 +2512,8 @@ mod tests {
             Transaction::write_read(IOEXP_ADDR_LOW, vec![0x03], vec![0b0000_0000]),
         ];
         let i2cbus = Mock::new(&expectations);
-        let dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
-        let mut dev = SharedDevice::new(dev);
+        let dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
+        let dev = SharedDevice::new(dev);
 
         {
             let pins: [IoPin<
@@ -2104,11 +2563,8 @@ mod tests {
             Transaction::write(IOEXP_ADDR_LOW, vec![0x02, 0b0000_0000]),
         ];
         let i2cbus = Mock::new(&expectations);
-        let mut dev = Device::new(Pcal6416aDevice {
-            addr_pin: AddrPinState::Low,
-            i2cbus,
-        });
-        let mut dev = SharedDevice::new(dev);
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
+        let dev = SharedDevice::new(dev);
 
         {
             let mut pins: [IoPin<
@@ -0,0 +1,180 @@
+//! Support for expanders behind a TCA9548A-style I2C multiplexer.
+//!
+//! Boards that fit several expanders at the same address put them on different downstream
+//! channels of a multiplexer. [`I2cMux`] owns the upstream bus and hands out a [`MuxChannel`]
+//! per downstream channel. Each channel implements the async I2C trait, so it can be used as the
+//! bus of a [`crate::Pcal6416aDevice`]: the channel is selected before every register access,
+//! and the select is skipped when the channel is already active. The bus stays locked from the
+//! select until the transaction completes, so several [`crate::SharedDevice`]s behind the same
+//! mux can be used concurrently.
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::mutex::{Mutex, MutexGuard};
+use embedded_hal_async::i2c::{Operation, SevenBitAddress};
+
+/// Number of downstream channels of a TCA9548A
+pub const MUX_CHANNELS: u8 = 8;
+
+struct State<I2c> {
+    i2c: I2c,
+    /// Channel currently routed, `None` when unknown
+    active: Option<u8>,
+}
+
+/// A TCA9548A-style I2C multiplexer owning the upstream bus.
+pub struct I2cMux<I2c: embedded_hal_async::i2c::I2c, M: RawMutex> {
+    address: u8,
+    state: Mutex<M, State<I2c>>,
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex> I2cMux<I2c, M> {
+    /// Create a multiplexer at `address` on the upstream bus `i2c`.
+    ///
+    /// The routed channel is assumed unknown, so the first access selects it.
+    pub const fn new(address: u8, i2c: I2c) -> Self {
+        Self {
+            address,
+            state: Mutex::new(State { i2c, active: None }),
+        }
+    }
+
+    /// Get a bus handle for downstream `channel`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel` is not below [`MUX_CHANNELS`]
+    #[must_use]
+    pub fn channel(&self, channel: u8) -> MuxChannel<'_, I2c, M> {
+        assert!(channel < MUX_CHANNELS, "Invalid mux channel");
+        MuxChannel { mux: self, channel }
+    }
+
+    /// Disconnect all downstream channels
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn deselect(&self) -> Result<(), I2c::Error> {
+        let mut state = self.state.lock().await;
+        state.active = None;
+        state.i2c.write(self.address, &[0]).await?;
+        Ok(())
+    }
+
+    /// Release the upstream bus
+    pub fn into_inner(self) -> I2c {
+        self.state.into_inner().i2c
+    }
+}
+
+/// Bus handle for one downstream channel of an [`I2cMux`].
+pub struct MuxChannel<'a, I2c: embedded_hal_async::i2c::I2c, M: RawMutex> {
+    mux: &'a I2cMux<I2c, M>,
+    channel: u8,
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex> MuxChannel<'_, I2c, M> {
+    /// Get the downstream channel number
+    #[must_use]
+    pub const fn number(&self) -> u8 {
+        self.channel
+    }
+
+    /// Lock the upstream bus with this channel routed
+    async fn select(&self) -> Result<MutexGuard<'_, M, State<I2c>>, I2c::Error> {
+        let mut state = self.mux.state.lock().await;
+
+        if state.active != Some(self.channel) {
+            // Forget the routing until the select is acknowledged, so a failed
+            // select is retried on the next access
+            state.active = None;
+            state.i2c.write(self.mux.address, &[1 << self.channel]).await?;
+            state.active = Some(self.channel);
+        }
+
+        Ok(state)
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex> embedded_hal::i2c::ErrorType for MuxChannel<'_, I2c, M> {
+    type Error = I2c::Error;
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex> embedded_hal_async::i2c::I2c<SevenBitAddress>
+    for MuxChannel<'_, I2c, M>
+{
+    async fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.select().await?.i2c.read(address, read).await
+    }
+
+    async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        self.select().await?.i2c.write(address, write).await
+    }
+
+    async fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        self.select().await?.i2c.write_read(address, write, read).await
+    }
+
+    async fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        self.select().await?.i2c.transaction(address, operations).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use super::*;
+    use crate::{AddrPinState, Device, Pcal6416aDevice, Pin, Port, SharedDevice};
+
+    const MUX_ADDR: u8 = 0x70;
+
+    #[tokio::test]
+    async fn selects_channel_once() {
+        let expectations = vec![
+            Transaction::write(MUX_ADDR, vec![0b0000_0001]),
+            Transaction::write_read(0x20, vec![0x00], vec![0b0000_0001]),
+            // Channel 0 still routed
+            Transaction::write_read(0x20, vec![0x00], vec![0b0000_0000]),
+            Transaction::write(MUX_ADDR, vec![0b0000_1000]),
+            Transaction::write_read(0x20, vec![0x00], vec![0b0000_0001]),
+            Transaction::write(MUX_ADDR, vec![0b0000_0001]),
+            Transaction::write_read(0x20, vec![0x01], vec![0b0000_0000]),
+        ];
+        let mux: I2cMux<_, NoopRawMutex> = I2cMux::new(MUX_ADDR, Mock::new(&expectations));
+
+        {
+            let first: SharedDevice<_, NoopRawMutex> =
+                SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, mux.channel(0))));
+            let second: SharedDevice<_, NoopRawMutex> =
+                SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, mux.channel(3))));
+
+            let mut first = first.device.lock().await;
+            let mut second = second.device.lock().await;
+            assert!(first.is_pin_high_async(Port::Port0, Pin::Pin0).await.unwrap());
+            assert!(first.is_pin_low_async(Port::Port0, Pin::Pin0).await.unwrap());
+            assert!(second.is_pin_high_async(Port::Port0, Pin::Pin0).await.unwrap());
+            assert!(first.is_pin_low_async(Port::Port1, Pin::Pin0).await.unwrap());
+        }
+
+        mux.into_inner().done();
+    }
+
+    #[tokio::test]
+    async fn failed_select_is_retried() {
+        let expectations = vec![
+            Transaction::write(MUX_ADDR, vec![0b0000_0100]).with_error(embedded_hal::i2c::ErrorKind::Other),
+            Transaction::write(MUX_ADDR, vec![0b0000_0100]),
+            Transaction::write_read(0x21, vec![0x02], vec![0b0000_0001]),
+        ];
+        let mux: I2cMux<_, NoopRawMutex> = I2cMux::new(MUX_ADDR, Mock::new(&expectations));
+
+        {
+            let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::High, mux.channel(2)));
+            assert!(dev.is_pin_set_high_async(Port::Port0, Pin::Pin0).await.is_err());
+            assert!(dev.is_pin_set_high_async(Port::Port0, Pin::Pin0).await.unwrap());
+        }
+
+        mux.into_inner().done();
+    }
+}
@@ -0,0 +1,471 @@
+//! Low-power park mode.
+//!
+//! Parking saves every writable register of the expander, then reconfigures the pins for
+//! the lowest leakage in one go: inputs with pull resistors enabled, output registers low,
+//! output drive strength at its minimum and interrupts masked, apart from the wake pins.
+//! Unparking writes the saved registers back, so the expander resumes exactly as it was.
+//!
+//! Locked pins, see [`Pcal6416aDevice::lock_pins`], are left as they are. A frozen
+//! configuration cannot be parked: the first configuration write fails with
+//! [`Pcal6416aError::Locked`]. When a write fails part way, the device stays parked and
+//! unparking restores it.
+//!
+//! On parts without agile I/O only the Output, Polarity Inversion and Configuration
+//! registers exist, so parked pins are inputs without pull resistors.
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+
+use crate::interrupt::all_pins;
+use crate::{AGILE_IO_BASE, Bank, Chip, Device, Pcal6416aDevice, Pcal6416aError, Port, SharedDevice, bank_register};
+
+/// Low-power configuration applied by parking
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ParkConfig {
+    /// Pins left as they are, bit `port * 8 + pin`
+    pub keep: u32,
+    /// Parked pins pulled up rather than down
+    pub pull_up: u32,
+    /// Pins left unmasked as wake sources, masked pins otherwise keep their mask
+    pub wake: u32,
+}
+
+/// Registers saved by parking, each written in a single transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    /// All ports of a bank
+    Bank(Bank),
+    /// Output drive strength of a port, two bits per pin
+    Drive(Port),
+    /// Output Port Configuration register
+    OutputConfig,
+}
+
+/// Writable banks, in the order of their saved values
+const BANKS: [Bank; 7] = [
+    Bank::Output,
+    Bank::PolarityInversion,
+    Bank::Configuration,
+    Bank::PullEnable,
+    Bank::PullSelect,
+    Bank::InputLatch,
+    Bank::InterruptMask,
+];
+
+/// Registers written by parking, in order: pulls first, so inputs never float
+const PARK_ORDER: [Block; 9] = [
+    Block::Bank(Bank::PullSelect),
+    Block::Bank(Bank::PullEnable),
+    Block::Bank(Bank::Configuration),
+    Block::Bank(Bank::Output),
+    Block::Drive(Port::Port0),
+    Block::Drive(Port::Port1),
+    Block::Drive(Port::Port2),
+    Block::Bank(Bank::InterruptMask),
+    Block::OutputConfig,
+];
+
+/// Number of saved registers
+const BLOCKS: usize = BANKS.len() + 4;
+
+/// Registers saved by parking, in restore order: outputs first, so they drive their level
+/// as soon as they are outputs again, and interrupts last
+const RESTORE_ORDER: [Block; BLOCKS] = [
+    Block::Bank(Bank::Output),
+    Block::Drive(Port::Port0),
+    Block::Drive(Port::Port1),
+    Block::Drive(Port::Port2),
+    Block::OutputConfig,
+    Block::Bank(Bank::PolarityInversion),
+    Block::Bank(Bank::Configuration),
+    Block::Bank(Bank::PullEnable),
+    Block::Bank(Bank::PullSelect),
+    Block::Bank(Bank::InputLatch),
+    Block::Bank(Bank::InterruptMask),
+];
+
+impl Block {
+    /// Index of the saved value
+    fn slot(self) -> usize {
+        match self {
+            Self::Bank(bank) => BANKS.iter().position(|&b| b == bank).unwrap_or(0),
+            Self::Drive(port) => BANKS.len() + usize::from(port.index()),
+            Self::OutputConfig => BLOCKS - 1,
+        }
+    }
+
+    /// Whether the chip has the register, agile I/O included if `agile`
+    fn exists<C: Chip>(self, agile: bool) -> bool {
+        match self {
+            Self::Bank(bank) => agile || matches!(bank, Bank::Output | Bank::PolarityInversion | Bank::Configuration),
+            Self::Drive(port) => agile && usize::from(port.index()) < C::PORTS,
+            Self::OutputConfig => agile,
+        }
+    }
+
+    /// Command byte and length in bytes of the transfer
+    fn transfer<C: Chip>(self) -> (u8, usize) {
+        match self {
+            Self::Bank(bank) => (bank_register::<C>(bank), C::PORTS),
+            Self::Drive(port) => ((AGILE_IO_BASE + 2 * port.index()) | C::AUTO_INCREMENT, 2),
+            Self::OutputConfig => (C::OUTPUT_CONFIG, 1),
+        }
+    }
+
+    /// Value of the register while parked, `parked` being the pins to park
+    fn parked(self, saved: u32, parked: u32, config: &ParkConfig) -> u32 {
+        match self {
+            Self::Bank(Bank::Output) => saved & !parked,
+            Self::Bank(Bank::PullSelect) => (saved & !parked) | (config.pull_up & parked),
+            Self::Bank(Bank::Configuration | Bank::PullEnable) => saved | parked,
+            Self::Bank(Bank::InterruptMask) => (saved | parked) & !config.wake,
+            Self::Drive(port) => {
+                let pins = parked >> (8 * port.index()) & 0xFF;
+                (0..8)
+                    .filter(|pin| pins & 1 << pin != 0)
+                    .fold(saved, |acc, pin| acc & !(0b11 << (2 * pin)))
+            }
+            Self::Bank(_) | Self::OutputConfig => saved,
+        }
+    }
+}
+
+/// Registers saved by parking
+#[derive(Clone, Copy)]
+pub(crate) struct Snapshot {
+    values: [u32; BLOCKS],
+}
+
+impl<I2c, C: Chip> Pcal6416aDevice<I2c, C> {
+    /// Get whether the device is parked
+    #[must_use]
+    pub const fn is_parked(&self) -> bool {
+        self.parked.is_some()
+    }
+
+    /// Pins parked by `config`
+    fn parked_pins(&self, config: &ParkConfig) -> u32 {
+        all_pins::<C>() & !config.keep & !self.locked_pins()
+    }
+}
+
+impl<I2c: embedded_hal::i2c::I2c, C: Chip> Device<Pcal6416aDevice<I2c, C>> {
+    /// Save every writable register, then apply the low-power configuration `config`
+    ///
+    /// Parking a parked device applies `config` over the registers saved the first time,
+    /// writing every register again.
+    /// # Errors
+    ///
+    /// Will return `Err` if a register to change is locked or underlying I2C bus operation fails
+    pub fn park(&mut self, config: &ParkConfig) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let agile = self.interface.variant.has_agile_io();
+        let (snapshot, reparking) = if let Some(snapshot) = self.interface.parked {
+            (snapshot, true)
+        } else {
+            let mut values = [0; BLOCKS];
+            for block in RESTORE_ORDER.into_iter().filter(|block| block.exists::<C>(agile)) {
+                values[block.slot()] = self.read_block(block)?;
+            }
+            let snapshot = Snapshot { values };
+            self.interface.parked = Some(snapshot);
+            (snapshot, false)
+        };
+
+        let parked = self.interface.parked_pins(config);
+        for block in PARK_ORDER.into_iter().filter(|block| block.exists::<C>(agile)) {
+            let saved = snapshot.values[block.slot()];
+            let value = block.parked(saved, parked, config);
+            if reparking || value != saved {
+                self.write_block(block, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write back the registers saved by [`Device::park`]
+    ///
+    /// Does nothing if the device is not parked.
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails, the device then stays parked
+    pub fn unpark(&mut self) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let Some(snapshot) = self.interface.parked else {
+            return Ok(());
+        };
+
+        let agile = self.interface.variant.has_agile_io();
+        for block in RESTORE_ORDER.into_iter().filter(|block| block.exists::<C>(agile)) {
+            self.write_block(block, snapshot.values[block.slot()])?;
+        }
+        self.interface.parked = None;
+        Ok(())
+    }
+
+    #[allow(clippy::cast_possible_truncation)] // at most 3 bytes
+    fn read_block(&mut self, block: Block) -> Result<u32, Pcal6416aError<I2c::Error>> {
+        let (address, len) = block.transfer::<C>();
+        let mut data = [0u8; 4];
+        device_driver::RegisterInterface::read_register(
+            &mut self.interface,
+            address,
+            8 * len as u32,
+            &mut data[..len],
+        )?;
+        Ok(u32::from_le_bytes(data))
+    }
+
+    #[allow(clippy::cast_possible_truncation)] // at most 3 bytes
+    fn write_block(&mut self, block: Block, value: u32) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let (address, len) = block.transfer::<C>();
+        let data = value.to_le_bytes();
+        device_driver::RegisterInterface::write_register(&mut self.interface, address, 8 * len as u32, &data[..len])
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, C: Chip> Device<Pcal6416aDevice<I2c, C>> {
+    /// Save every writable register, then apply the low-power configuration `config`, see
+    /// [`Device::park`] (async version)
+    /// # Errors
+    ///
+    /// Will return `Err` if a register to change is locked or underlying I2C bus operation fails
+    pub async fn park_async(&mut self, config: &ParkConfig) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let agile = self.interface.variant.has_agile_io();
+        let (snapshot, reparking) = if let Some(snapshot) = self.interface.parked {
+            (snapshot, true)
+        } else {
+            let mut values = [0; BLOCKS];
+            for block in RESTORE_ORDER.into_iter().filter(|block| block.exists::<C>(agile)) {
+                values[block.slot()] = self.read_block_async(block).await?;
+            }
+            let snapshot = Snapshot { values };
+            self.interface.parked = Some(snapshot);
+            (snapshot, false)
+        };
+
+        let parked = self.interface.parked_pins(config);
+        for block in PARK_ORDER.into_iter().filter(|block| block.exists::<C>(agile)) {
+            let saved = snapshot.values[block.slot()];
+            let value = block.parked(saved, parked, config);
+            if reparking || value != saved {
+                self.write_block_async(block, value).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write back the registers saved by [`Device::park_async`] (async version)
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails, the device then stays parked
+    pub async fn unpark_async(&mut self) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let Some(snapshot) = self.interface.parked else {
+            return Ok(());
+        };
+
+        let agile = self.interface.variant.has_agile_io();
+        for block in RESTORE_ORDER.into_iter().filter(|block| block.exists::<C>(agile)) {
+            self.write_block_async(block, snapshot.values[block.slot()]).await?;
+        }
+        self.interface.parked = None;
+        Ok(())
+    }
+
+    #[allow(clippy::cast_possible_truncation)] // at most 3 bytes
+    async fn read_block_async(&mut self, block: Block) -> Result<u32, Pcal6416aError<I2c::Error>> {
+        let (address, len) = block.transfer::<C>();
+        let mut data = [0u8; 4];
+        device_driver::AsyncRegisterInterface::read_register(
+            &mut self.interface,
+            address,
+            8 * len as u32,
+            &mut data[..len],
+        )
+        .await?;
+        Ok(u32::from_le_bytes(data))
+    }
+
+    #[allow(clippy::cast_possible_truncation)] // at most 3 bytes
+    async fn write_block_async(&mut self, block: Block, value: u32) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let (address, len) = block.transfer::<C>();
+        let data = value.to_le_bytes();
+        device_driver::AsyncRegisterInterface::write_register(
+            &mut self.interface,
+            address,
+            8 * len as u32,
+            &data[..len],
+        )
+        .await
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> SharedDevice<I2c, M, C> {
+    /// Park the device, see [`Device::park`]
+    ///
+    /// The device stays locked for the whole batch. Pin waits relying on demand masking
+    /// should end first, as they would unmask their pins again.
+    /// # Errors
+    ///
+    /// Will return `Err` if a register to change is locked or underlying I2C bus operation fails
+    pub async fn park(&self, config: &ParkConfig) -> Result<(), Pcal6416aError<I2c::Error>> {
+        self.device.lock().await.park_async(config).await
+    }
+
+    /// Restore the device parked by [`SharedDevice::park`]
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails, the device then stays parked
+    pub async fn unpark(&self) -> Result<(), Pcal6416aError<I2c::Error>> {
+        self.device.lock().await.unpark_async().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use crate::AddrPinState;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn park_and_restore() {
+        let i2cbus = Mock::new(&[
+            // Saved
+            Transaction::write_read(0x20, vec![0x02], vec![0x0F, 0x80]),
+            Transaction::write_read(0x20, vec![0x40], vec![0xFF, 0xFF]),
+            Transaction::write_read(0x20, vec![0x42], vec![0xFF, 0xFF]),
+            Transaction::write_read(0x20, vec![0x4F], vec![0x00]),
+            Transaction::write_read(0x20, vec![0x04], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x06], vec![0xF0, 0x7F]),
+            Transaction::write_read(0x20, vec![0x46], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x48], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x44], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x4A], vec![0xFF, 0xFF]),
+            // Parked, pin 1_7 kept, port 0 pulled up, pin 0_0 unmasked
+            Transaction::write(0x20, vec![0x48, 0xFF, 0x00]),
+            Transaction::write(0x20, vec![0x46, 0xFF, 0x7F]),
+            Transaction::write(0x20, vec![0x06, 0xFF, 0x7F]),
+            Transaction::write(0x20, vec![0x02, 0x00, 0x80]),
+            Transaction::write(0x20, vec![0x40, 0x00, 0x00]),
+            Transaction::write(0x20, vec![0x42, 0x00, 0xC0]),
+            Transaction::write(0x20, vec![0x4A, 0xFE, 0xFF]),
+            // Restored
+            Transaction::write(0x20, vec![0x02, 0x0F, 0x80]),
+            Transaction::write(0x20, vec![0x40, 0xFF, 0xFF]),
+            Transaction::write(0x20, vec![0x42, 0xFF, 0xFF]),
+            Transaction::write(0x20, vec![0x4F, 0x00]),
+            Transaction::write(0x20, vec![0x04, 0x00, 0x00]),
+            Transaction::write(0x20, vec![0x06, 0xF0, 0x7F]),
+            Transaction::write(0x20, vec![0x46, 0x00, 0x00]),
+            Transaction::write(0x20, vec![0x48, 0x00, 0x00]),
+            Transaction::write(0x20, vec![0x44, 0x00, 0x00]),
+            Transaction::write(0x20, vec![0x4A, 0xFF, 0xFF]),
+        ]);
+        let dev: SharedDevice<_, NoopRawMutex> =
+            SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus)));
+
+        dev.park(&ParkConfig {
+            keep: 0x8000,
+            pull_up: 0x00FF,
+            wake: 0x0001,
+        })
+        .await
+        .unwrap();
+        assert!(dev.device.lock().await.interface.is_parked());
+        dev.unpark().await.unwrap();
+        assert!(!dev.device.lock().await.interface.is_parked());
+
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+
+    #[test]
+    fn repark_writes_every_register() {
+        let i2cbus = Mock::new(&[
+            // Saved
+            Transaction::write_read(0x20, vec![0x02], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x40], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x42], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x4F], vec![0x00]),
+            Transaction::write_read(0x20, vec![0x04], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x06], vec![0xFF, 0xFF]),
+            Transaction::write_read(0x20, vec![0x46], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x48], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x44], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x4A], vec![0xFF, 0xFF]),
+            // Parked, only the pull resistors change
+            Transaction::write(0x20, vec![0x46, 0xFF, 0xFF]),
+            // Parked again, port 0 pulled up
+            Transaction::write(0x20, vec![0x48, 0xFF, 0x00]),
+            Transaction::write(0x20, vec![0x46, 0xFF, 0xFF]),
+            Transaction::write(0x20, vec![0x06, 0xFF, 0xFF]),
+            Transaction::write(0x20, vec![0x02, 0x00, 0x00]),
+            Transaction::write(0x20, vec![0x40, 0x00, 0x00]),
+            Transaction::write(0x20, vec![0x42, 0x00, 0x00]),
+            Transaction::write(0x20, vec![0x4A, 0xFF, 0xFF]),
+            Transaction::write(0x20, vec![0x4F, 0x00]),
+            // Restored from the first save
+            Transaction::write(0x20, vec![0x02, 0x00, 0x00]),
+            Transaction::write(0x20, vec![0x40, 0x00, 0x00]),
+            Transaction::write(0x20, vec![0x42, 0x00, 0x00]),
+            Transaction::write(0x20, vec![0x4F, 0x00]),
+            Transaction::write(0x20, vec![0x04, 0x00, 0x00]),
+            Transaction::write(0x20, vec![0x06, 0xFF, 0xFF]),
+            Transaction::write(0x20, vec![0x46, 0x00, 0x00]),
+            Transaction::write(0x20, vec![0x48, 0x00, 0x00]),
+            Transaction::write(0x20, vec![0x44, 0x00, 0x00]),
+            Transaction::write(0x20, vec![0x4A, 0xFF, 0xFF]),
+        ]);
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
+
+        dev.park(&ParkConfig::default()).unwrap();
+        dev.park(&ParkConfig {
+            pull_up: 0x00FF,
+            ..ParkConfig::default()
+        })
+        .unwrap();
+        assert!(dev.interface.is_parked());
+        dev.unpark().unwrap();
+        assert!(!dev.interface.is_parked());
+        // Nothing left to restore
+        dev.unpark().unwrap();
+
+        dev.interface.i2cbus.done();
+    }
+
+    #[test]
+    fn locks_limit_parking() {
+        let i2cbus = Mock::new(&[
+            // Saved
+            Transaction::write_read(0x20, vec![0x02], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x40], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x42], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x4F], vec![0x00]),
+            Transaction::write_read(0x20, vec![0x04], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x06], vec![0xFF, 0xFF]),
+            Transaction::write_read(0x20, vec![0x46], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x48], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x44], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x4A], vec![0xFF, 0xFF]),
+            // Parked around pin 1_7, its pull resistor read back first
+            Transaction::write_read(0x20, vec![0x46], vec![0x00, 0x00]),
+            Transaction::write(0x20, vec![0x46, 0xFF, 0x7F]),
+            // Frozen, changing the pull resistor selection fails
+            Transaction::write_read(0x20, vec![0x48], vec![0x00, 0x00]),
+        ]);
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus).with_locked_pins(0x8000));
+
+        dev.park(&ParkConfig::default()).unwrap();
+        dev.interface.freeze();
+        assert!(matches!(
+            dev.park(&ParkConfig {
+                pull_up: 0x00FF,
+                ..ParkConfig::default()
+            }),
+            Err(Pcal6416aError::Locked)
+        ));
+        assert!(dev.interface.is_parked());
+
+        dev.interface.i2cbus.done();
+    }
+}
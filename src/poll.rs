@@ -0,0 +1,123 @@
+//! Polling fallback for boards without the INT output routed.
+//!
+//! [`InputPoller`] samples all inputs of a [`SharedDevice`] at a fixed interval and feeds
+//! the samples through the same snapshot as the interrupt path, so pin waits, edge filters
+//! and event subscriptions work unchanged.
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embedded_hal_async::delay::DelayNs;
+
+use crate::{Chip, Pcal6416aError, SharedDevice};
+
+/// Default interval between two samples, in milliseconds
+pub const DEFAULT_POLL_INTERVAL_MS: u32 = 10;
+
+/// Periodic input sampler standing in for the INT line.
+///
+/// # Example
+/// ```ignore
+/// let shared = SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus)));
+/// let mut poller = InputPoller::new(&shared, embassy_time::Delay).with_interval_ms(5);
+///
+/// // In a dedicated task
+/// poller.run().await?;
+/// ```
+pub struct InputPoller<'a, D: DelayNs, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> {
+    device: &'a SharedDevice<I2c, M, C>,
+    delay: D,
+    interval_ms: u32,
+}
+
+impl<'a, D: DelayNs, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> InputPoller<'a, D, I2c, M, C> {
+    /// Create a poller for `device`, waiting with `delay` between samples
+    pub const fn new(device: &'a SharedDevice<I2c, M, C>, delay: D) -> Self {
+        Self {
+            device,
+            delay,
+            interval_ms: DEFAULT_POLL_INTERVAL_MS,
+        }
+    }
+
+    /// Set the interval between two samples
+    #[must_use]
+    pub const fn with_interval_ms(mut self, interval_ms: u32) -> Self {
+        self.interval_ms = interval_ms;
+        self
+    }
+
+    /// Get the interval between two samples, in milliseconds
+    #[must_use]
+    pub const fn interval_ms(&self) -> u32 {
+        self.interval_ms
+    }
+
+    /// Change the interval between two samples
+    pub const fn set_interval_ms(&mut self, interval_ms: u32) {
+        self.interval_ms = interval_ms;
+    }
+
+    /// Sample the inputs once
+    ///
+    /// Returns the pins with a reported edge, bit `port * 8 + pin`.
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn poll(&self) -> Result<u32, Pcal6416aError<I2c::Error>> {
+        Ok(self.device.sample_inputs().await?.pins())
+    }
+
+    /// Sample the inputs every interval, forever
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn run(&mut self) -> Result<core::convert::Infallible, Pcal6416aError<I2c::Error>> {
+        loop {
+            self.poll().await?;
+            self.delay.delay_ms(self.interval_ms).await;
+        }
+    }
+
+    /// Release the delay provider
+    pub fn into_inner(self) -> D {
+        self.delay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, Transaction as DelayTransaction};
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use super::*;
+    use crate::{AddrPinState, Device, Edge, Pcal6416aDevice};
+
+    #[tokio::test]
+    async fn polled_edges_are_published() {
+        let i2cbus = Mock::new(&[
+            Transaction::write_read(0x20, vec![0x00], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x00, 0x00]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x00, 0x02]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x00, 0x02]).with_error(embedded_hal::i2c::ErrorKind::Other),
+        ]);
+        let delay = CheckedDelay::new(&[
+            DelayTransaction::async_delay_ms(5),
+            DelayTransaction::async_delay_ms(5),
+            DelayTransaction::async_delay_ms(5),
+        ]);
+        let dev: SharedDevice<_, NoopRawMutex> =
+            SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus)));
+
+        let mut events = dev.subscribe(u32::MAX).unwrap();
+        let mut poller = InputPoller::new(&dev, delay).with_interval_ms(5);
+        assert!(poller.run().await.is_err());
+        assert_eq!(
+            events.try_next().map(|event| (event.index(), event.edge)),
+            Some((9, Edge::Rising))
+        );
+        assert!(events.try_next().is_none());
+
+        poller.into_inner().done();
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+}
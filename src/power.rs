@@ -0,0 +1,344 @@
+//! Power sequencing.
+//!
+//! A [`PowerSequence`] runs an ordered list of [`PowerStep`]s, typically load switch
+//! enables and power-good inputs: driving a pin, waiting, and waiting for an input with a
+//! timeout. Powering down walks the completed steps in reverse, driving each pin to the
+//! opposite level and waiting for each input to return to the opposite level. Every step
+//! goes through the [`SharedDevice`] and releases it in between, so the other pins stay
+//! usable while a sequence runs.
+//!
+//! Input waits follow the pin's edges when the device is serviced and re-sample the pin at
+//! least every poll interval otherwise.
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embedded_hal_async::delay::DelayNs;
+
+use crate::timeout::with_timeout;
+use crate::{Bank, Chip, IoPin, Pcal6416aError, Pin, Port, SharedDevice};
+
+/// Default longest time between two samples of an input waited for, in milliseconds
+pub const DEFAULT_POWER_POLL_INTERVAL_MS: u32 = 10;
+
+/// One step of a [`PowerSequence`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PowerStep {
+    /// Drive a pin, making it an output if needed
+    Set {
+        /// Port of the pin
+        port: Port,
+        /// Pin within the port
+        pin: Pin,
+        /// Level driven on power-up, the opposite one on power-down
+        high: bool,
+    },
+    /// Wait
+    Delay {
+        /// Time to wait, in milliseconds
+        ms: u32,
+    },
+    /// Wait for an input to reach a level
+    WaitFor {
+        /// Port of the pin
+        port: Port,
+        /// Pin within the port
+        pin: Pin,
+        /// Level waited for on power-up, the opposite one on power-down
+        high: bool,
+        /// Longest wait, in milliseconds
+        timeout_ms: u32,
+    },
+}
+
+impl PowerStep {
+    /// Get the pin the step acts on, if any
+    #[must_use]
+    pub const fn pin(&self) -> Option<(Port, Pin)> {
+        match *self {
+            Self::Set { port, pin, .. } | Self::WaitFor { port, pin, .. } => Some((port, pin)),
+            Self::Delay { .. } => None,
+        }
+    }
+}
+
+/// Why a [`PowerStep`] failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PowerFailure<E> {
+    /// The input did not reach its level in time
+    Timeout,
+    /// Error accessing the expander
+    Device(Pcal6416aError<E>),
+}
+
+/// Failure of a [`PowerSequence`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PowerError<E> {
+    /// Index of the failed step in the sequence
+    pub step: usize,
+    /// Pin of the failed step, if any
+    pub pin: Option<(Port, Pin)>,
+    /// What went wrong
+    pub cause: PowerFailure<E>,
+}
+
+/// Ordered power-up and reverse power-down of rails controlled through a [`SharedDevice`].
+///
+/// # Example
+/// ```ignore
+/// const RAILS: &[PowerStep] = &[
+///     PowerStep::Set { port: Port::Port0, pin: Pin::Pin0, high: true },
+///     PowerStep::WaitFor { port: Port::Port1, pin: Pin::Pin0, high: true, timeout_ms: 20 },
+///     PowerStep::Delay { ms: 5 },
+///     PowerStep::Set { port: Port::Port0, pin: Pin::Pin1, high: true },
+/// ];
+///
+/// let mut sequence = PowerSequence::new(&shared, embassy_time::Delay, RAILS);
+/// if let Err(error) = sequence.power_up().await {
+///     defmt::error!("step {} failed on {}: {}", error.step, error.pin, error.cause);
+///     sequence.power_down().await?;
+/// }
+/// ```
+pub struct PowerSequence<'a, 's, D: DelayNs, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> {
+    shared: &'a SharedDevice<I2c, M, C>,
+    delay: D,
+    steps: &'s [PowerStep],
+    /// Steps completed by power-up and not yet undone
+    completed: usize,
+    poll_interval_ms: u32,
+}
+
+impl<'a, 's, D: DelayNs, I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> PowerSequence<'a, 's, D, I2c, M, C> {
+    /// Create a sequence of `steps` on `shared`, timing it with `delay`
+    pub const fn new(shared: &'a SharedDevice<I2c, M, C>, delay: D, steps: &'s [PowerStep]) -> Self {
+        Self {
+            shared,
+            delay,
+            steps,
+            completed: 0,
+            poll_interval_ms: DEFAULT_POWER_POLL_INTERVAL_MS,
+        }
+    }
+
+    /// Set the longest time between two samples of an input waited for
+    #[must_use]
+    pub const fn with_poll_interval_ms(mut self, poll_interval_ms: u32) -> Self {
+        self.poll_interval_ms = poll_interval_ms;
+        self
+    }
+
+    /// Get the number of steps completed by power-up and not undone by power-down
+    #[must_use]
+    pub const fn completed(&self) -> usize {
+        self.completed
+    }
+
+    /// Run the steps not completed yet, in order, stopping at the first failure
+    ///
+    /// After a failure, [`PowerSequence::power_down`] undoes the steps that completed and
+    /// calling this again retries from the failed step.
+    /// # Errors
+    ///
+    /// Will return `Err` naming the step if an input times out or underlying I2C bus
+    /// operation fails
+    pub async fn power_up(&mut self) -> Result<(), PowerError<I2c::Error>> {
+        while let Some(&step) = self.steps.get(self.completed) {
+            self.run(self.completed, step, false).await?;
+            self.completed += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Undo the completed steps in reverse order
+    ///
+    /// Inputs not reaching their level in time do not stop the power-down, so every rail
+    /// still gets switched off; the first of them is reported once done. A bus error stops
+    /// the power-down at the failing step.
+    /// # Errors
+    ///
+    /// Will return `Err` naming the step if an input times out or underlying I2C bus
+    /// operation fails
+    pub async fn power_down(&mut self) -> Result<(), PowerError<I2c::Error>> {
+        let mut timed_out = None;
+        while let Some(index) = self.completed.checked_sub(1) {
+            match self.run(index, self.steps[index], true).await {
+                Err(
+                    error @ PowerError {
+                        cause: PowerFailure::Timeout,
+                        ..
+                    },
+                ) => {
+                    timed_out.get_or_insert(error);
+                }
+                result => result?,
+            }
+            self.completed = index;
+        }
+
+        timed_out.map_or(Ok(()), Err)
+    }
+
+    /// Release the delay provider
+    pub fn into_inner(self) -> D {
+        self.delay
+    }
+
+    /// Run step `index`, inverted when powering down
+    async fn run(&mut self, index: usize, step: PowerStep, invert: bool) -> Result<(), PowerError<I2c::Error>> {
+        let error = |cause| PowerError {
+            step: index,
+            pin: step.pin(),
+            cause,
+        };
+
+        match step {
+            PowerStep::Set { port, pin, high } => self
+                .set(port, pin, high != invert)
+                .await
+                .map_err(|e| error(PowerFailure::Device(e))),
+            PowerStep::Delay { ms } => {
+                self.delay.delay_ms(ms).await;
+                Ok(())
+            }
+            PowerStep::WaitFor {
+                port,
+                pin,
+                high,
+                timeout_ms,
+            } => match self.wait_for(port, pin, high != invert, timeout_ms).await {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(error(PowerFailure::Timeout)),
+                Err(e) => Err(error(PowerFailure::Device(e))),
+            },
+        }
+    }
+
+    /// Drive a pin, then make it an output if it is not one yet
+    async fn set(&self, port: Port, pin: Pin, high: bool) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let mut device = self.shared.device.lock().await;
+        if high {
+            device.set_pin_high_async(port, pin).await?;
+        } else {
+            device.set_pin_low_async(port, pin).await?;
+        }
+
+        let config = device.read_port_async(Bank::Configuration, port).await?;
+        if config & pin.mask() != 0 {
+            device
+                .write_port_async(Bank::Configuration, port, config & !pin.mask())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Wait up to `timeout_ms` for an input to reach `high`, returning whether it did
+    async fn wait_for(
+        &mut self,
+        port: Port,
+        pin: Pin,
+        high: bool,
+        timeout_ms: u32,
+    ) -> Result<bool, Pcal6416aError<I2c::Error>> {
+        let input = IoPin::new(port, pin, self.shared);
+        let edges = (high, !high);
+        let mut waited_ms = 0;
+        while waited_ms < timeout_ms {
+            let slice_ms = (timeout_ms - waited_ms).min(self.poll_interval_ms);
+            if let Some(result) = with_timeout(input.wait_for(edges, Some(high)), self.delay.delay_ms(slice_ms)).await {
+                return result.map(|()| true);
+            }
+            waited_ms += slice_ms;
+        }
+
+        Ok(input.is_high_async().await? == high)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, Transaction as DelayTransaction};
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use super::*;
+    use crate::{AddrPinState, Device, Pcal6416aDevice};
+
+    const STEPS: &[PowerStep] = &[
+        PowerStep::Set {
+            port: Port::Port0,
+            pin: Pin::Pin0,
+            high: true,
+        },
+        PowerStep::WaitFor {
+            port: Port::Port0,
+            pin: Pin::Pin1,
+            high: true,
+            timeout_ms: 20,
+        },
+        PowerStep::Delay { ms: 5 },
+        PowerStep::Set {
+            port: Port::Port0,
+            pin: Pin::Pin2,
+            high: true,
+        },
+        PowerStep::WaitFor {
+            port: Port::Port0,
+            pin: Pin::Pin3,
+            high: true,
+            timeout_ms: 10,
+        },
+    ];
+
+    #[tokio::test]
+    async fn failed_power_up_is_unwound() {
+        let i2cbus = Mock::new(&[
+            // Power-up
+            Transaction::write_read(0x20, vec![0x02], vec![0x00]),
+            Transaction::write(0x20, vec![0x02, 0x01]),
+            Transaction::write_read(0x20, vec![0x06], vec![0xFF]),
+            Transaction::write(0x20, vec![0x06, 0xFE]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x02]),
+            Transaction::write_read(0x20, vec![0x02], vec![0x01]),
+            Transaction::write(0x20, vec![0x02, 0x05]),
+            Transaction::write_read(0x20, vec![0x06], vec![0xFE]),
+            Transaction::write(0x20, vec![0x06, 0xFA]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x02]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x02]),
+            // Power-down
+            Transaction::write_read(0x20, vec![0x02], vec![0x05]),
+            Transaction::write(0x20, vec![0x02, 0x01]),
+            Transaction::write_read(0x20, vec![0x06], vec![0xFA]),
+            Transaction::write_read(0x20, vec![0x00], vec![0x00]),
+            Transaction::write_read(0x20, vec![0x02], vec![0x01]),
+            Transaction::write(0x20, vec![0x02, 0x00]),
+            Transaction::write_read(0x20, vec![0x06], vec![0xFA]),
+        ]);
+        let delay = CheckedDelay::new(&[
+            DelayTransaction::async_delay_ms(5),
+            DelayTransaction::async_delay_ms(10),
+            DelayTransaction::async_delay_ms(5),
+        ]);
+        let dev: SharedDevice<_, NoopRawMutex> =
+            SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus)));
+
+        let mut sequence = PowerSequence::new(&dev, delay, STEPS);
+        assert_eq!(
+            sequence.power_up().await,
+            Err(PowerError {
+                step: 4,
+                pin: Some((Port::Port0, Pin::Pin3)),
+                cause: PowerFailure::Timeout,
+            })
+        );
+        assert_eq!(sequence.completed(), 4);
+
+        sequence.power_down().await.unwrap();
+        assert_eq!(sequence.completed(), 0);
+
+        sequence.into_inner().done();
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+}
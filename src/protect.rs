@@ -0,0 +1,255 @@
+//! Pin reservation and write protection.
+//!
+//! Locking a pin on a [`Pcal6416aDevice`] protects its direction, output level, polarity,
+//! pull resistor and drive settings: the bits of a locked pin can no longer change, and a
+//! write that would change them fails with [`Pcal6416aError::Locked`] without reaching
+//! the bus. Writes to the other pins sharing a register are still accepted, so the register
+//! is read back before such a write to compare the locked bits. Whole banks can be locked
+//! too, and [`Pcal6416aDevice::freeze`] locks the configuration of every pin at once.
+//!
+//! The protection is enforced by the register interface, so it covers every API writing
+//! through it, [`IoPin`](crate::IoPin) included. Locks cannot be lifted; they last as long
+//! as the interface. The Input Latch and Interrupt Mask registers are only protected when
+//! their bank is locked explicitly, as event dispatching and demand masking write them.
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+
+use crate::{AGILE_IO_BASE, Bank, Chip, Pcal6416aDevice, Port, SharedDevice};
+
+/// Banks holding one bit per pin protected by a pin lock
+const PIN_BANKS: [Bank; 5] = [
+    Bank::Output,
+    Bank::PolarityInversion,
+    Bank::Configuration,
+    Bank::PullEnable,
+    Bank::PullSelect,
+];
+
+/// Banks locked by a freeze
+const CONFIG_BANKS: [Bank; 4] = [
+    Bank::PolarityInversion,
+    Bank::Configuration,
+    Bank::PullEnable,
+    Bank::PullSelect,
+];
+
+/// All banks, in the order of their bits in [`Protection::banks`]
+const BANKS: [Bank; 9] = [
+    Bank::Input,
+    Bank::Output,
+    Bank::PolarityInversion,
+    Bank::Configuration,
+    Bank::InputLatch,
+    Bank::PullEnable,
+    Bank::PullSelect,
+    Bank::InterruptMask,
+    Bank::InterruptStatus,
+];
+
+/// Locked pins and registers of a device
+pub(crate) struct Protection {
+    /// Locked pins, bit `port * 8 + pin`
+    pins: u32,
+    /// Locked banks, one bit per entry of [`BANKS`]
+    banks: u16,
+    /// Configuration of every pin locked
+    frozen: bool,
+}
+
+impl Protection {
+    pub(crate) const fn new() -> Self {
+        Self {
+            pins: 0,
+            banks: 0,
+            frozen: false,
+        }
+    }
+
+    /// Whether a write of `len` bytes at `address` touches protected bits
+    pub(crate) fn protects<C: Chip>(&self, address: u8, len: usize) -> bool {
+        (address & !C::AUTO_INCREMENT..)
+            .take(len)
+            .any(|register| self.protected::<C>(register) != 0)
+    }
+
+    /// Whether writing `data` over `current` at `address` leaves the protected bits as
+    /// they are
+    pub(crate) fn allows<C: Chip>(&self, address: u8, current: &[u8], data: &[u8]) -> bool {
+        (address & !C::AUTO_INCREMENT..)
+            .zip(current.iter().zip(data))
+            .all(|(register, (current, new))| (current ^ new) & self.protected::<C>(register) == 0)
+    }
+
+    /// Bits of the register at `register` that must not change
+    #[allow(clippy::cast_possible_truncation)] // port bytes of the pin mask
+    fn protected<C: Chip>(&self, register: u8) -> u8 {
+        if let Some((bit, bank, port)) = bank_port::<C>(register) {
+            let whole = self.banks & 1 << bit != 0 || (self.frozen && CONFIG_BANKS.contains(&bank));
+            if whole {
+                0xFF
+            } else if PIN_BANKS.contains(&bank) {
+                (self.pins >> (8 * port.index())) as u8
+            } else {
+                0
+            }
+        } else if register == C::OUTPUT_CONFIG {
+            if self.frozen {
+                0xFF
+            } else {
+                (0..C::PORTS)
+                    .filter(|port| self.pins >> (8 * port) & 0xFF != 0)
+                    .fold(0, |acc, port| acc | 1 << port)
+            }
+        } else if let Some(offset) = register.checked_sub(AGILE_IO_BASE)
+            && usize::from(offset) < C::PINS / 4
+        {
+            // Output drive strength, two bits for each of four pins
+            if self.frozen {
+                0xFF
+            } else {
+                (0..4)
+                    .filter(|pin| self.pins & 1 << (4 * offset + pin) != 0)
+                    .fold(0, |acc, pin| acc | 0b11 << (2 * pin))
+            }
+        } else {
+            0
+        }
+    }
+
+    /// Pins whose Output bits a safe state may change, bit `port * 8 + pin`, and whether it
+    /// may write the Configuration registers
+    pub(crate) fn safe_scope(&self) -> (u32, bool) {
+        let locked = |bank| {
+            BANKS
+                .iter()
+                .position(|&b| b == bank)
+                .is_some_and(|bit| self.banks & 1 << bit != 0)
+        };
+        let pins = if locked(Bank::Output) { 0 } else { !self.pins };
+        (pins, !self.frozen && !locked(Bank::Configuration))
+    }
+}
+
+/// Bank and port of the register at `register`, with the bit of the bank in
+/// [`Protection::banks`]
+fn bank_port<C: Chip>(register: u8) -> Option<(u8, Bank, Port)> {
+    (0u8..).zip(BANKS).find_map(|(bit, bank)| {
+        [Port::Port0, Port::Port1, Port::Port2]
+            .into_iter()
+            .take(C::PORTS)
+            .find(|&port| C::register(bank, port) == register)
+            .map(|port| (bit, bank, port))
+    })
+}
+
+impl<I2c, C: Chip> Pcal6416aDevice<I2c, C> {
+    /// Lock pins, see [`Pcal6416aDevice::lock_pins`]
+    #[must_use]
+    pub fn with_locked_pins(mut self, pins: u32) -> Self {
+        self.lock_pins(pins);
+        self
+    }
+
+    /// Lock the direction, output level, polarity, pull resistor and drive settings of
+    /// `pins`, bit `port * 8 + pin`
+    ///
+    /// Set up the pins before locking them; the locks last as long as the interface.
+    pub const fn lock_pins(&mut self, pins: u32) {
+        self.protection.pins |= pins;
+    }
+
+    /// Lock all registers of `bank`
+    pub fn lock_bank(&mut self, bank: Bank) {
+        if let Some(bit) = BANKS.iter().position(|&b| b == bank) {
+            self.protection.banks |= 1 << bit;
+        }
+    }
+
+    /// Lock the configuration of every pin: direction, polarity, pull resistors, drive
+    /// strength and output port configuration
+    ///
+    /// Output levels of the pins not locked stay writable.
+    pub const fn freeze(&mut self) {
+        self.protection.frozen = true;
+    }
+
+    /// Get the locked pins, bit `port * 8 + pin`
+    #[must_use]
+    pub const fn locked_pins(&self) -> u32 {
+        self.protection.pins
+    }
+
+    /// Get whether the configuration is frozen
+    #[must_use]
+    pub const fn is_frozen(&self) -> bool {
+        self.protection.frozen
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> SharedDevice<I2c, M, C> {
+    /// Lock pins of the device, see [`Pcal6416aDevice::lock_pins`]
+    pub async fn lock_pins(&self, pins: u32) {
+        self.device.lock().await.interface.lock_pins(pins);
+    }
+
+    /// Lock a bank of the device, see [`Pcal6416aDevice::lock_bank`]
+    pub async fn lock_bank(&self, bank: Bank) {
+        self.device.lock().await.interface.lock_bank(bank);
+    }
+
+    /// Lock the configuration of every pin, see [`Pcal6416aDevice::freeze`]
+    pub async fn freeze(&self) {
+        self.device.lock().await.interface.freeze();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use crate::{AddrPinState, Device, Pcal6416aError};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn locked_writes_are_rejected() {
+        let i2cbus = Mock::new(&[
+            // Pin 0_1 of the port with locked pin 0_0 driven, locked bit read back unchanged
+            Transaction::write_read(0x20, vec![0x02], vec![0x01]),
+            Transaction::write_read(0x20, vec![0x02], vec![0x01]),
+            Transaction::write(0x20, vec![0x02, 0x03]),
+            // Pin 0_0 driven low
+            Transaction::write_read(0x20, vec![0x02], vec![0x03]),
+            Transaction::write_read(0x20, vec![0x02], vec![0x03]),
+            // Drive strength of pin 0_0
+            Transaction::write_read(0x20, vec![0x40], vec![0xFF]),
+            // Frozen direction
+            Transaction::write_read(0x20, vec![0x07], vec![0xFE]),
+            Transaction::write_read(0x20, vec![0x07], vec![0xFE]),
+            // Output level of pin 1_0 still writable
+            Transaction::write_read(0x20, vec![0x03], vec![0x00]),
+            Transaction::write(0x20, vec![0x03, 0x01]),
+        ]);
+        let dev: SharedDevice<_, NoopRawMutex> = SharedDevice::new(Device::new(
+            Pcal6416aDevice::new(AddrPinState::Low, i2cbus).with_locked_pins(0x0001),
+        ));
+
+        let pins = dev.split();
+        pins[1].set_high_async().await.unwrap();
+        assert_eq!(pins[0].set_low_async().await, Err(Pcal6416aError::Locked));
+        {
+            let mut device = dev.device.lock().await;
+            assert_eq!(
+                device_driver::AsyncRegisterInterface::write_register(&mut device.interface, 0x40, 8, &[0x00]).await,
+                Err(Pcal6416aError::Locked)
+            );
+        }
+
+        dev.freeze().await;
+        assert_eq!(pins[8].set_as_input_async().await, Err(Pcal6416aError::Locked));
+        pins[8].set_high_async().await.unwrap();
+
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+}
@@ -0,0 +1,274 @@
+//! Timed pulses and one-shot outputs.
+//!
+//! A one-shot drives a pin to a level, switching it to an output if needed, and records
+//! what it changed in a [`OneShot`]. Ending the one-shot puts the pin back: the previous
+//! output level, and the input direction if it was an input. A pulse is a one-shot ended
+//! after a delay. The device is only held while the registers are written, so other pins
+//! stay usable during a pulse through a [`SharedDevice`].
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+
+use crate::{Bank, Chip, Device, IoPin, Pcal6416aDevice, Pcal6416aError, Pin, Port};
+
+/// Pin state saved by a one-shot, restored when it ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[must_use = "a one-shot lasts until it is ended"]
+pub struct OneShot {
+    port: Port,
+    pin: Pin,
+    /// Output register bit before the one-shot
+    was_high: bool,
+    /// The pin was configured as an input
+    was_input: bool,
+}
+
+impl OneShot {
+    /// Get the Port enum of the pin
+    #[must_use]
+    pub const fn port(&self) -> Port {
+        self.port
+    }
+
+    /// Get the Pin enum of the pin
+    #[must_use]
+    pub const fn pin(&self) -> Pin {
+        self.pin
+    }
+
+    /// Output register value of the port with the saved bit put back
+    const fn restore_output(self, output: u8) -> u8 {
+        if self.was_high {
+            output | self.pin.mask()
+        } else {
+            output & !self.pin.mask()
+        }
+    }
+}
+
+/// Output register value of a port with the bit of `pin` set to `high`
+const fn drive(output: u8, pin: Pin, high: bool) -> u8 {
+    if high {
+        output | pin.mask()
+    } else {
+        output & !pin.mask()
+    }
+}
+
+impl<I2c: embedded_hal::i2c::I2c, C: Chip> Device<Pcal6416aDevice<I2c, C>> {
+    /// Drive a pin high or low until [`Device::end_one_shot`], making it an output first
+    /// if needed
+    ///
+    /// The output level is written before the direction, so the pin never drives its
+    /// previous level.
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub fn start_one_shot(&mut self, port: Port, pin: Pin, high: bool) -> Result<OneShot, Pcal6416aError<I2c::Error>> {
+        let output = self.read_port(Bank::Output, port)?;
+        let config = self.read_port(Bank::Configuration, port)?;
+        self.write_port(Bank::Output, port, drive(output, pin, high))?;
+        let was_input = config & pin.mask() != 0;
+        if was_input {
+            self.write_port(Bank::Configuration, port, config & !pin.mask())?;
+        }
+
+        Ok(OneShot {
+            port,
+            pin,
+            was_high: output & pin.mask() != 0,
+            was_input,
+        })
+    }
+
+    /// Restore the pin state saved by `one_shot`
+    ///
+    /// A pin that was an input is released before its output register is restored.
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub fn end_one_shot(&mut self, one_shot: OneShot) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let OneShot { port, pin, .. } = one_shot;
+        if one_shot.was_input {
+            self.modify_port(Bank::Configuration, port, |config| config | pin.mask())?;
+        }
+        self.modify_port(Bank::Output, port, |output| one_shot.restore_output(output))
+    }
+
+    /// Drive a pin high or low for `duration_ms`, then restore it, see
+    /// [`Device::start_one_shot`]
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub fn pulse_pin(
+        &mut self,
+        port: Port,
+        pin: Pin,
+        high: bool,
+        duration_ms: u32,
+        delay: &mut impl embedded_hal::delay::DelayNs,
+    ) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let one_shot = self.start_one_shot(port, pin, high)?;
+        delay.delay_ms(duration_ms);
+        self.end_one_shot(one_shot)
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, C: Chip> Device<Pcal6416aDevice<I2c, C>> {
+    /// Drive a pin high or low until [`Device::end_one_shot_async`], see
+    /// [`Device::start_one_shot`] (async version)
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn start_one_shot_async(
+        &mut self,
+        port: Port,
+        pin: Pin,
+        high: bool,
+    ) -> Result<OneShot, Pcal6416aError<I2c::Error>> {
+        let output = self.read_port_async(Bank::Output, port).await?;
+        let config = self.read_port_async(Bank::Configuration, port).await?;
+        self.write_port_async(Bank::Output, port, drive(output, pin, high))
+            .await?;
+        let was_input = config & pin.mask() != 0;
+        if was_input {
+            self.write_port_async(Bank::Configuration, port, config & !pin.mask())
+                .await?;
+        }
+
+        Ok(OneShot {
+            port,
+            pin,
+            was_high: output & pin.mask() != 0,
+            was_input,
+        })
+    }
+
+    /// Restore the pin state saved by `one_shot`, see [`Device::end_one_shot`] (async version)
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn end_one_shot_async(&mut self, one_shot: OneShot) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let OneShot { port, pin, .. } = one_shot;
+        if one_shot.was_input {
+            self.modify_port_async(Bank::Configuration, port, |config| config | pin.mask())
+                .await?;
+        }
+        self.modify_port_async(Bank::Output, port, |output| one_shot.restore_output(output))
+            .await
+    }
+
+    /// Drive a pin high or low for `duration_ms`, then restore it, see
+    /// [`Device::start_one_shot`] (async version)
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn pulse_pin_async(
+        &mut self,
+        port: Port,
+        pin: Pin,
+        high: bool,
+        duration_ms: u32,
+        delay: &mut impl embedded_hal_async::delay::DelayNs,
+    ) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let one_shot = self.start_one_shot_async(port, pin, high).await?;
+        delay.delay_ms(duration_ms).await;
+        self.end_one_shot_async(one_shot).await
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> IoPin<'_, I2c, M, C> {
+    /// Drive this pin high or low until [`IoPin::end_one_shot`], see [`Device::start_one_shot`]
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn start_one_shot(&self, high: bool) -> Result<OneShot, Pcal6416aError<I2c::Error>> {
+        self.shared
+            .device
+            .lock()
+            .await
+            .start_one_shot_async(self.port, self.pin, high)
+            .await
+    }
+
+    /// Restore the state saved by `one_shot`, see [`Device::end_one_shot`]
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn end_one_shot(&self, one_shot: OneShot) -> Result<(), Pcal6416aError<I2c::Error>> {
+        self.shared.device.lock().await.end_one_shot_async(one_shot).await
+    }
+
+    /// Drive this pin high or low for `duration_ms`, then restore it
+    ///
+    /// The device is released during the delay, so other pins stay usable.
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn pulse(
+        &self,
+        high: bool,
+        duration_ms: u32,
+        delay: &mut impl embedded_hal_async::delay::DelayNs,
+    ) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let one_shot = self.start_one_shot(high).await?;
+        delay.delay_ms(duration_ms).await;
+        self.end_one_shot(one_shot).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, Transaction as DelayTransaction};
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use super::*;
+    use crate::{AddrPinState, SharedDevice};
+
+    #[tokio::test]
+    async fn pulse_input_pin() {
+        let i2cbus = Mock::new(&[
+            // Pin 1_2 is an input with its output bit set
+            Transaction::write_read(0x20, vec![0x03], vec![0x84]),
+            Transaction::write_read(0x20, vec![0x07], vec![0xFF]),
+            Transaction::write(0x20, vec![0x03, 0x80]),
+            Transaction::write(0x20, vec![0x07, 0xFB]),
+            // Another pin written during the pulse
+            Transaction::write_read(0x20, vec![0x03], vec![0x80]),
+            Transaction::write(0x20, vec![0x03, 0x00]),
+            // Released first, then output bit restored
+            Transaction::write_read(0x20, vec![0x07], vec![0xFB]),
+            Transaction::write(0x20, vec![0x07, 0xFF]),
+            Transaction::write_read(0x20, vec![0x03], vec![0x00]),
+            Transaction::write(0x20, vec![0x03, 0x04]),
+        ]);
+        let dev: SharedDevice<_, NoopRawMutex> =
+            SharedDevice::new(Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus)));
+
+        let pins = dev.split();
+        let one_shot = pins[10].start_one_shot(false).await.unwrap();
+        pins[15].set_low_async().await.unwrap();
+        pins[10].end_one_shot(one_shot).await.unwrap();
+
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+
+    #[test]
+    fn blocking_pulse_output_pin() {
+        let i2cbus = embedded_hal_mock::eh1::i2c::Mock::new(&[
+            Transaction::write_read(0x20, vec![0x02], vec![0x01]),
+            Transaction::write_read(0x20, vec![0x06], vec![0xFE]),
+            Transaction::write(0x20, vec![0x02, 0x00]),
+            Transaction::write_read(0x20, vec![0x02], vec![0x00]),
+            Transaction::write(0x20, vec![0x02, 0x01]),
+        ]);
+        let mut delay = CheckedDelay::new(&[DelayTransaction::blocking_delay_ms(10)]);
+        let mut dev = Device::new(Pcal6416aDevice::new(AddrPinState::Low, i2cbus));
+
+        dev.pulse_pin(Port::Port0, Pin::Pin0, false, 10, &mut delay).unwrap();
+
+        delay.done();
+        dev.interface.i2cbus.done();
+    }
+}
@@ -0,0 +1,194 @@
+//! Interrupt storm protection.
+//!
+//! A floating or chattering input can keep INT asserted continuously. With protection
+//! enabled, the interrupt layer counts the edges of each pin over a time window; a pin
+//! exceeding the limit has its Interrupt Mask bit set, its edges ignored and a
+//! [`crate::EventKind::Storm`] event published. [`SharedDevice::rearm`] restores it.
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+
+use crate::interrupt::{MAX_PINS, bits};
+use crate::{Bank, Chip, Device, Pcal6416aDevice, Pcal6416aError, SharedDevice};
+
+/// Edge rate limit for storm protection
+#[derive(Debug, Clone, Copy)]
+pub struct StormConfig {
+    /// Most edges accepted from a pin within one window
+    pub max_edges: u16,
+    /// Length of the counting window, in milliseconds
+    pub window_ms: u32,
+    /// Monotonic clock in milliseconds, e.g. `|| embassy_time::Instant::now().as_millis()`
+    pub now_ms: fn() -> u64,
+}
+
+/// Per-pin edge rate tracking
+pub(crate) struct StormTracker {
+    config: Option<StormConfig>,
+    /// Start of the current window per pin
+    window_start: [u64; MAX_PINS],
+    /// Edges seen in the current window per pin
+    count: [u16; MAX_PINS],
+    /// Pins over the limit, masked until re-armed
+    tripped: u32,
+}
+
+impl StormTracker {
+    pub(crate) const fn new() -> Self {
+        Self {
+            config: None,
+            window_start: [0; MAX_PINS],
+            count: [0; MAX_PINS],
+            tripped: 0,
+        }
+    }
+
+    /// Pins over the limit
+    pub(crate) const fn tripped(&self) -> u32 {
+        self.tripped
+    }
+
+    /// Count one edge for each pin of `changed`, returning the pins newly over the limit
+    pub(crate) fn track(&mut self, changed: u32) -> u32 {
+        let Some(config) = self.config else {
+            return 0;
+        };
+
+        let now = (config.now_ms)();
+        let mut tripped = 0;
+        for index in bits(changed & !self.tripped) {
+            if now.wrapping_sub(self.window_start[index]) >= u64::from(config.window_ms) {
+                self.window_start[index] = now;
+                self.count[index] = 0;
+            }
+
+            self.count[index] = self.count[index].saturating_add(1);
+            if self.count[index] > config.max_edges {
+                tripped |= 1 << index;
+            }
+        }
+
+        self.tripped |= tripped;
+        tripped
+    }
+
+    /// Clear the tripped state of `pins`, returning those that were tripped
+    fn rearm(&mut self, pins: u32) -> u32 {
+        let rearmed = self.tripped & pins;
+        self.tripped &= !pins;
+        for index in bits(rearmed) {
+            self.count[index] = 0;
+        }
+        rearmed
+    }
+}
+
+impl<I2c: embedded_hal_async::i2c::I2c, M: RawMutex, C: Chip> SharedDevice<I2c, M, C> {
+    /// Enable storm protection with `config`, or disable it with `None`
+    ///
+    /// Disabling protection does not re-arm pins already masked.
+    pub fn set_storm_protection(&self, config: Option<StormConfig>) {
+        self.with_events(|events| events.storm.config = config);
+    }
+
+    /// Get the pins masked by storm protection, bit `port * 8 + pin`
+    #[must_use]
+    pub fn stormed_pins(&self) -> u32 {
+        self.with_events(|events| events.storm.tripped())
+    }
+
+    /// Re-arm the pins of `pins` masked by storm protection, clearing their Interrupt Mask bits
+    ///
+    /// With demand masking enabled the pins are only unmasked while waited on.
+    /// # Errors
+    ///
+    /// Will return `Err` if underlying I2C bus operation fails
+    pub async fn rearm(&self, pins: u32) -> Result<(), Pcal6416aError<I2c::Error>> {
+        let mut device = self.device.lock().await;
+        let rearmed = self.with_events(|events| events.storm.rearm(pins));
+        if self.demand_masking() {
+            self.sync_demand(&mut device).await?;
+        } else if rearmed != 0 && device.interface.variant.has_agile_io() {
+            let mask = device.read_bank_async(Bank::InterruptMask).await?;
+            device.write_bank_async(Bank::InterruptMask, mask & !rearmed).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Set the Interrupt Mask bits of the pins newly tripped by storm protection
+pub(crate) async fn mask_stormed<I2c: embedded_hal_async::i2c::I2c, C: Chip>(
+    device: &mut Device<Pcal6416aDevice<I2c, C>>,
+    stormed: u32,
+) -> Result<(), Pcal6416aError<I2c::Error>> {
+    if stormed != 0 && device.interface.variant.has_agile_io() {
+        let mask = device.read_bank_async(Bank::InterruptMask).await?;
+        device.write_bank_async(Bank::InterruptMask, mask | stormed).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use super::*;
+    use crate::{AddrPinState, EventKind};
+
+    static NOW: AtomicU64 = AtomicU64::new(0);
+
+    fn now_ms() -> u64 {
+        NOW.load(Ordering::Relaxed)
+    }
+
+    #[tokio::test]
+    async fn chattering_pin_is_masked() {
+        let mut expectations = vec![Transaction::write_read(0x20, vec![0x00], vec![0x00, 0x00])];
+        for levels in [0x04, 0x00, 0x04] {
+            expectations.push(Transaction::write_read(0x20, vec![0x4C], vec![0x04, 0x00]));
+            expectations.push(Transaction::write_read(0x20, vec![0x00], vec![levels, 0x00]));
+        }
+        expectations.extend([
+            Transaction::write_read(0x20, vec![0x4A], vec![0x00, 0x00]),
+            Transaction::write(0x20, vec![0x4A, 0x04, 0x00]),
+            // Masked: further edges are ignored
+            Transaction::write_read(0x20, vec![0x00], vec![0x00, 0x00]),
+            // Re-armed
+            Transaction::write_read(0x20, vec![0x4A], vec![0x04, 0x00]),
+            Transaction::write(0x20, vec![0x4A, 0x00, 0x00]),
+        ]);
+        let dev: SharedDevice<_, NoopRawMutex> = SharedDevice::new(Device::new(Pcal6416aDevice::new(
+            AddrPinState::Low,
+            Mock::new(&expectations),
+        )));
+
+        dev.set_storm_protection(Some(StormConfig {
+            max_edges: 2,
+            window_ms: 100,
+            now_ms,
+        }));
+        let mut events = dev.subscribe(u32::MAX).unwrap();
+        dev.sample_inputs().await.unwrap();
+
+        assert_eq!(dev.service_interrupt().await.unwrap(), 0x0004);
+        assert_eq!(dev.service_interrupt().await.unwrap(), 0x0004);
+        NOW.store(50, Ordering::Relaxed);
+        assert_eq!(dev.service_interrupt().await.unwrap(), 0x0004);
+        assert_eq!(dev.stormed_pins(), 0x0004);
+        assert_eq!(dev.sample_inputs().await.unwrap().pins(), 0);
+
+        let kinds: Vec<_> = core::iter::from_fn(|| events.try_next())
+            .map(|event| event.kind)
+            .collect();
+        assert_eq!(kinds, [EventKind::Edge, EventKind::Edge, EventKind::Storm]);
+
+        dev.rearm(0x0004).await.unwrap();
+        assert_eq!(dev.stormed_pins(), 0);
+
+        dev.device.lock().await.interface.i2cbus.done();
+    }
+}
@@ -0,0 +1,23 @@
+//! Racing a future against a timeout.
+
+use core::future::{Future, poll_fn};
+use core::pin::pin;
+use core::task::Poll;
+
+/// Run `future` until it completes or `timeout` does, returning `None` on timeout
+///
+/// `future` is polled first, so it wins when both are ready.
+pub(crate) async fn with_timeout<F: Future>(future: F, timeout: impl Future<Output = ()>) -> Option<F::Output> {
+    let mut future = pin!(future);
+    let mut timeout = pin!(timeout);
+    poll_fn(|cx| {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return Poll::Ready(Some(output));
+        }
+        if timeout.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(None);
+        }
+        Poll::Pending
+    })
+    .await
+}